@@ -0,0 +1,310 @@
+//! Compact binary encoding of [`TrigramData`]. All integers are LEB128 varints unless noted.
+//!
+//! ```text
+//! magic      b"TTDB"
+//! version    u8
+//! metadata   len, JSON of the dataset metadata
+//! sources    count, per source:
+//!              name len + 1 (0 if unnamed), UTF-8 name
+//!              len, JSON of the source metadata
+//! trigrams   count, sorted by position, per trigram:
+//!              row, col of each key as u8
+//!              run count, per run: source, len
+//!              every sample in µs, zigzag delta coded against the previous one
+//! ```
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    error::LoadErrorKind,
+    load::{FileSummary, LoadReport},
+    mapping::Pos,
+    metadata::DatasetMetadata,
+    sample::{Micros, Samples, Source, SourceId},
+    TrigramData,
+};
+
+pub const MAGIC: &[u8; 4] = b"TTDB";
+pub const VERSION: u8 = 1;
+
+/// Whether `bytes` starts like a binary dataset.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn write_varint<W: Write>(w: &mut W, mut n: u64) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn write_bytes<W: Write>(w: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_varint(w, bytes.len() as u64)?;
+    w.write_all(bytes)
+}
+
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    (n >> 1) as i64 ^ -((n & 1) as i64)
+}
+
+fn invalid(message: impl Into<String>) -> LoadErrorKind {
+    LoadErrorKind::Binary(message.into())
+}
+
+struct Reader<R> {
+    inner: R,
+}
+
+impl<R: Read> Reader<R> {
+    fn u8(&mut self) -> Result<u8, LoadErrorKind> {
+        let mut buf = [0];
+        self.inner
+            .read_exact(&mut buf)
+            .map_err(|e| invalid(format!("unexpected end of data: {e}")))?;
+        Ok(buf[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, LoadErrorKind> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            n |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(invalid("varint is too long"))
+    }
+
+    fn usize(&mut self) -> Result<usize, LoadErrorKind> {
+        self.varint().and_then(|n| {
+            usize::try_from(n).map_err(|_| invalid(format!("length {n} is too large")))
+        })
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>, LoadErrorKind> {
+        let mut buf = Vec::new();
+        (&mut self.inner)
+            .take(len as u64)
+            .read_to_end(&mut buf)
+            .map_err(LoadErrorKind::Io)?;
+        match buf.len() == len {
+            true => Ok(buf),
+            false => Err(invalid("unexpected end of data")),
+        }
+    }
+
+    fn string(&mut self, len: usize) -> Result<String, LoadErrorKind> {
+        String::from_utf8(self.bytes(len)?).map_err(|e| invalid(e.to_string()))
+    }
+
+    fn metadata(&mut self) -> Result<DatasetMetadata, LoadErrorKind> {
+        let len = self.usize()?;
+        serde_json::from_slice(&self.bytes(len)?).map_err(|e| invalid(e.to_string()))
+    }
+}
+
+impl TrigramData {
+    /// Writes the dataset to `path` in the binary format, see [`write_binary`](Self::write_binary).
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_binary(&mut w)?;
+        w.flush()
+    }
+
+    /// Writes the dataset in the compact binary format described in the [module docs](self).
+    /// Unlike the JSON format this keeps the source of every sample.
+    pub fn write_binary<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        write_bytes(&mut w, &serde_json::to_vec(&self.metadata)?)?;
+
+        write_varint(&mut w, self.sources.len() as u64)?;
+        for source in self.sources.iter() {
+            match &source.name {
+                Some(name) => {
+                    write_varint(&mut w, name.len() as u64 + 1)?;
+                    w.write_all(name.as_bytes())?;
+                }
+                None => write_varint(&mut w, 0)?,
+            }
+            write_bytes(&mut w, &serde_json::to_vec(&source.metadata)?)?;
+        }
+
+        let mut trigrams = self.trigrams.iter().collect::<Vec<_>>();
+        trigrams.sort_unstable_by_key(|(trigram, _)| **trigram);
+
+        write_varint(&mut w, trigrams.len() as u64)?;
+        for (trigram, samples) in trigrams {
+            for Pos { row, col } in trigram {
+                let packed = [*row, *col].map(|n| u8::try_from(n).ok());
+                let [Some(row), Some(col)] = packed else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("can't pack position {row},{col}"),
+                    ));
+                };
+                w.write_all(&[row, col])?;
+            }
+
+            write_varint(&mut w, samples.runs().len() as u64)?;
+            for (source, len) in samples.runs() {
+                write_varint(&mut w, *source as u64)?;
+                write_varint(&mut w, *len as u64)?;
+            }
+
+            let mut prev = 0i64;
+            for Micros(us) in samples.values() {
+                write_varint(&mut w, zigzag(*us as i64 - prev))?;
+                prev = *us as i64;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a binary dataset into `self`, appending its sources after the existing ones.
+    pub(crate) fn read_binary_into<R: Read>(&mut self, r: R) -> Result<LoadReport, LoadErrorKind> {
+        let mut r = Reader { inner: r };
+
+        let magic = r.bytes(MAGIC.len())?;
+        if magic != MAGIC {
+            return Err(invalid("not a binary dataset"));
+        }
+
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(LoadErrorKind::UnsupportedVersion(version as u32));
+        }
+
+        let metadata = r.metadata()?;
+        let current = std::mem::take(&mut self.metadata);
+        let (merged, warnings) = current
+            .merge(metadata)
+            .map_err(LoadErrorKind::Incompatible)?;
+        self.metadata = merged;
+
+        let offset = self.sources.len() as SourceId;
+        let source_count = r.usize()?;
+        for _ in 0..source_count {
+            let name = match r.usize()? {
+                0 => None,
+                len => Some(r.string(len - 1)?),
+            };
+            let metadata = r.metadata()?;
+            self.sources.push(Source { name, metadata });
+        }
+
+        let trigram_count = r.usize()?;
+        let mut samples = 0;
+        for _ in 0..trigram_count {
+            let mut trigram = [Pos::default(); 3];
+            for pos in trigram.iter_mut() {
+                pos.row = r.u8()? as usize;
+                pos.col = r.u8()? as usize;
+                if pos.code().is_none() {
+                    return Err(invalid(format!("unknown key {pos}")));
+                }
+            }
+
+            let run_count = r.usize()?;
+            let mut runs = Vec::with_capacity(run_count.min(1024));
+            for _ in 0..run_count {
+                let source = SourceId::try_from(r.varint()?)
+                    .ok()
+                    .filter(|s| (*s as usize) < source_count)
+                    .ok_or_else(|| invalid("sample run refers to an unknown source"))?;
+                runs.push((source + offset, r.usize()?));
+            }
+
+            let len = runs
+                .iter()
+                .try_fold(0usize, |sum, (_, len)| sum.checked_add(*len))
+                .ok_or_else(|| invalid("sample runs are too long"))?;
+            let mut values = Vec::with_capacity(len.min(1 << 16));
+            let mut prev = 0i64;
+            for _ in 0..len {
+                prev = prev
+                    .checked_add(unzigzag(r.varint()?))
+                    .ok_or_else(|| invalid("sample is out of range"))?;
+                let us = u32::try_from(prev)
+                    .ok()
+                    .filter(|us| *us <= Micros::MAX.0)
+                    .ok_or_else(|| invalid(format!("invalid sample {prev} µs")))?;
+                values.push(Micros(us));
+            }
+
+            samples += values.len();
+            self.insert(trigram, Samples::from_runs(values, runs));
+        }
+
+        Ok(LoadReport {
+            files: vec![FileSummary {
+                path: None,
+                entries: trigram_count,
+                samples,
+            }],
+            warnings,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::LoadErrorKind,
+        mapping::Pos,
+        sample::{Micros, Samples},
+        testing::*,
+        TrigramData,
+    };
+
+    /// Writes a dataset with a single trigram and reads it back.
+    fn read_back(trigram: [Pos; 3], values: Vec<Micros>) -> Result<TrigramData, LoadErrorKind> {
+        let mut data = TrigramData::default();
+        data.add_source(None);
+        data.insert(trigram, Samples::new(0, values));
+
+        let mut bytes = Vec::new();
+        data.write_binary(&mut bytes).unwrap();
+        let mut loaded = TrigramData::default();
+        loaded.read_binary_into(bytes.as_slice()).map(|_| loaded)
+    }
+
+    #[test]
+    fn corrupt_samples_are_rejected() {
+        let trigram = crate::parse_trigram("KeyA,KeyB,KeyC").unwrap();
+        assert!(read_back(trigram, vec![Micros(86_000)]).is_ok());
+        assert!(read_back(trigram, vec![Micros(u32::MAX)]).is_err());
+
+        let unknown = Pos { row: 200, col: 200 };
+        assert!(read_back([unknown, trigram[1], trigram[2]], vec![Micros(86_000)]).is_err());
+    }
+
+    #[test]
+    fn save_binary_round_trips() {
+        let data = fixture();
+        let path = temp_path("save.ttd");
+        data.save_binary(&path).unwrap();
+        let loaded = TrigramData::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.metadata, loaded.metadata);
+        assert_same_samples(&data, &loaded);
+        assert_same_sources(&data, &loaded);
+    }
+}
@@ -0,0 +1,210 @@
+//! Dense storage for the samples of a [`MatrixData`], addressed by matrix indexes.
+//!
+//! A projection of `n` keys has `n³` possible trigrams, few enough to give every one of them a
+//! slot. All samples live in a single arena ordered by trigram, and a table of offsets into it
+//! finds the samples of any trigram in constant time. Iterating goes through the arena in order,
+//! which keeps repeated passes over the same data, like bootstrap resampling, fast.
+//!
+//! [`MatrixData`]: crate::MatrixData
+
+use std::collections::HashMap;
+
+use crate::sample::{Micros, Samples, SourceId};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenseTrigrams {
+    /// Number of keys in the matrix.
+    keys: usize,
+    /// Samples of the trigram at flat index `i` are at `offsets[i]..offsets[i + 1]`.
+    offsets: Vec<usize>,
+    values: Vec<Micros>,
+    sources: Vec<SourceId>,
+}
+
+impl Default for DenseTrigrams {
+    /// A matrix without keys, which still has the one offset every layout starts with.
+    fn default() -> Self {
+        Self::new(0, HashMap::new())
+    }
+}
+
+/// The samples of one trigram, borrowed from a [`DenseTrigrams`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TrigramSamples<'a> {
+    values: &'a [Micros],
+    sources: &'a [SourceId],
+}
+
+impl<'a> TrigramSamples<'a> {
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &'a [Micros] {
+        self.values
+    }
+
+    /// The source of every sample.
+    pub fn sources(&self) -> &'a [SourceId] {
+        self.sources
+    }
+
+    /// Every sample along with the source it came from.
+    pub fn iter(&self) -> impl Iterator<Item = (SourceId, Micros)> + 'a {
+        self.sources
+            .iter()
+            .copied()
+            .zip(self.values.iter().copied())
+    }
+
+    /// The samples whose source matches `f`.
+    pub fn filter_source(
+        &self,
+        f: impl Fn(SourceId) -> bool + 'a,
+    ) -> impl Iterator<Item = Micros> + 'a {
+        self.iter()
+            .filter_map(move |(source, value)| f(source).then_some(value))
+    }
+}
+
+impl DenseTrigrams {
+    /// Lays out the samples of trigrams of a matrix of `keys` keys. Indexes must be below `keys`.
+    pub(crate) fn new(keys: usize, trigrams: HashMap<[usize; 3], Samples>) -> Self {
+        let slots = keys.pow(3);
+        let mut offsets = vec![0; slots + 1];
+        for (indexes, samples) in trigrams.iter() {
+            offsets[flat(keys, indexes) + 1] += samples.len();
+        }
+        for i in 0..slots {
+            offsets[i + 1] += offsets[i];
+        }
+
+        let total = offsets[slots];
+        let mut values = vec![Micros::default(); total];
+        let mut sources = vec![0; total];
+        for (indexes, samples) in trigrams.iter() {
+            let start = offsets[flat(keys, indexes)];
+            for (i, (source, value)) in samples.iter().enumerate() {
+                values[start + i] = value;
+                sources[start + i] = source;
+            }
+        }
+
+        Self {
+            keys,
+            offsets,
+            values,
+            sources,
+        }
+    }
+
+    /// Number of trigrams with at least one sample.
+    pub fn len(&self) -> usize {
+        self.offsets.windows(2).filter(|w| w[0] < w[1]).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Number of samples over all trigrams.
+    pub fn sample_count(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The samples of a trigram, or `None` if it has none or an index is outside of the matrix.
+    pub fn get(&self, indexes: &[usize; 3]) -> Option<TrigramSamples<'_>> {
+        if indexes.iter().any(|i| *i >= self.keys) {
+            return None;
+        }
+
+        let samples = self.slot(flat(self.keys, indexes));
+        (!samples.is_empty()).then_some(samples)
+    }
+
+    /// Every trigram with at least one sample, ordered by index.
+    pub fn iter(&self) -> impl Iterator<Item = ([usize; 3], TrigramSamples<'_>)> + '_ {
+        (0..self.offsets.len() - 1)
+            .filter(|i| self.offsets[*i] < self.offsets[*i + 1])
+            .map(|i| (unflat(self.keys, i), self.slot(i)))
+    }
+
+    /// Keeps only the samples for which `f` returns true, and returns how many were removed.
+    pub(crate) fn retain(
+        &mut self,
+        mut f: impl FnMut(&[usize; 3], SourceId, Micros) -> bool,
+    ) -> usize {
+        let before = self.values.len();
+        let mut kept = 0;
+        let mut start = 0;
+
+        for i in 0..self.offsets.len() - 1 {
+            let (from, to) = (start, self.offsets[i + 1]);
+            let indexes = unflat(self.keys, i);
+            for j in from..to {
+                if f(&indexes, self.sources[j], self.values[j]) {
+                    self.values[kept] = self.values[j];
+                    self.sources[kept] = self.sources[j];
+                    kept += 1;
+                }
+            }
+            start = to;
+            self.offsets[i + 1] = kept;
+        }
+
+        self.values.truncate(kept);
+        self.sources.truncate(kept);
+        before - kept
+    }
+
+    fn slot(&self, i: usize) -> TrigramSamples<'_> {
+        let range = self.offsets[i]..self.offsets[i + 1];
+        TrigramSamples {
+            values: &self.values[range.clone()],
+            sources: &self.sources[range],
+        }
+    }
+}
+
+fn flat(keys: usize, [a, b, c]: &[usize; 3]) -> usize {
+    (a * keys + b) * keys + c
+}
+
+fn unflat(keys: usize, i: usize) -> [usize; 3] {
+    [i / (keys * keys), i / keys % keys, i % keys]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_and_single_trigram_layouts() {
+        let mut empty = DenseTrigrams::default();
+        assert!(empty.is_empty());
+        assert_eq!(empty.len(), 0);
+        assert_eq!(empty.iter().count(), 0);
+        assert_eq!(empty.get(&[0, 0, 0]), None);
+        assert_eq!(empty.retain(|_, _, _| false), 0);
+
+        let values = vec![Micros(90_000), Micros(120_000)];
+        let trigrams = HashMap::from([([1, 0, 1], Samples::new(3, values.clone()))]);
+        let mut single = DenseTrigrams::new(2, trigrams);
+        assert_eq!(single.len(), 1);
+        let found = single.iter().collect::<Vec<_>>();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, [1, 0, 1]);
+        assert_eq!(found[0].1.values(), values);
+        assert_eq!(found[0].1.sources(), [3, 3]);
+
+        assert_eq!(single.retain(|_, _, value| value < Micros(100_000)), 1);
+        assert_eq!(single.get(&[1, 0, 1]).unwrap().values(), [Micros(90_000)]);
+        assert_eq!(single.retain(|_, _, _| false), 1);
+        assert!(single.is_empty());
+        assert_eq!(single.iter().count(), 0);
+    }
+}
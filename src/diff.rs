@@ -0,0 +1,277 @@
+//! Comparing two datasets, e.g. the same typist before and after a week of practice.
+
+use crate::{
+    mapping::{trigram_str, Pos},
+    Avg, MatrixData, TrigramData,
+};
+
+/// A trigram that only appears in one of the datasets.
+#[derive(Clone, Debug)]
+pub struct TrigramChange {
+    pub trigram: [Pos; 3],
+    pub avg: Avg,
+}
+
+/// A trigram whose mean moved between the datasets.
+#[derive(Clone, Debug)]
+pub struct MeanShift {
+    pub trigram: [Pos; 3],
+    pub before: Avg,
+    pub after: Avg,
+}
+
+impl MeanShift {
+    /// How much the mean changed, in ms. Negative if the trigram got faster.
+    pub fn delta(&self) -> f64 {
+        self.after.mean() - self.before.mean()
+    }
+}
+
+/// A category of [`TrigramStats`](crate::TrigramStats) before and after.
+#[derive(Clone, Debug)]
+pub struct CategoryDelta {
+    pub name: &'static str,
+    pub before: Avg,
+    pub after: Avg,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DatasetDiff {
+    /// Threshold in ms above which mean shifts are listed.
+    pub threshold: f64,
+    pub added: Vec<TrigramChange>,
+    pub removed: Vec<TrigramChange>,
+    /// Sorted by the size of the shift, largest first.
+    pub shifted: Vec<MeanShift>,
+    pub categories: Vec<CategoryDelta>,
+}
+
+impl TrigramData {
+    /// Compares `self` to `after`, listing trigrams that were added or removed and those whose mean
+    /// shifted by more than `threshold` ms, along with the change of every stats category. Stats
+    /// are computed over the matrix `project` turns each dataset into, like
+    /// [`TrigramData::matrix_3x10`].
+    pub fn diff(
+        self,
+        after: TrigramData,
+        threshold: f64,
+        project: impl Fn(TrigramData) -> MatrixData,
+    ) -> DatasetDiff {
+        let mut diff = DatasetDiff {
+            threshold,
+            ..Default::default()
+        };
+
+        for (trigram, samples) in after.trigrams.iter() {
+            let avg = Avg::new(samples.values().to_vec());
+
+            match self.trigrams.get(trigram) {
+                Some(before) => {
+                    let shift = MeanShift {
+                        trigram: *trigram,
+                        before: Avg::new(before.values().to_vec()),
+                        after: avg,
+                    };
+                    if shift.delta().abs() > threshold {
+                        diff.shifted.push(shift);
+                    }
+                }
+                None => diff.added.push(TrigramChange {
+                    trigram: *trigram,
+                    avg,
+                }),
+            }
+        }
+
+        for (trigram, samples) in self.trigrams.iter() {
+            if !after.trigrams.contains_key(trigram) {
+                diff.removed.push(TrigramChange {
+                    trigram: *trigram,
+                    avg: Avg::new(samples.values().to_vec()),
+                });
+            }
+        }
+
+        diff.added.sort_unstable_by_key(|c| c.trigram);
+        diff.removed.sort_unstable_by_key(|c| c.trigram);
+        diff.shifted.sort_by(|a, b| {
+            b.delta()
+                .abs()
+                .total_cmp(&a.delta().abs())
+                .then(a.trigram.cmp(&b.trigram))
+        });
+
+        let before_stats = project(self).stats();
+        let after_stats = project(after).stats();
+        diff.categories = before_stats
+            .categories()
+            .into_iter()
+            .zip(after_stats.categories())
+            .map(|((name, before), (_, after))| CategoryDelta {
+                name,
+                before: before.clone(),
+                after: after.clone(),
+            })
+            .collect();
+
+        diff
+    }
+}
+
+impl std::fmt::Display for DatasetDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let changes = |f: &mut std::fmt::Formatter<'_>, title: &str, list: &[TrigramChange]| {
+            writeln!(f, "{title} ({}):", list.len())?;
+            for change in list {
+                writeln!(
+                    f,
+                    "  {:<30}mean: {:.1}  n: {}",
+                    trigram_str(&change.trigram),
+                    change.avg.mean(),
+                    change.avg.pop()
+                )?;
+            }
+            writeln!(f)
+        };
+
+        changes(f, "Added", &self.added)?;
+        changes(f, "Removed", &self.removed)?;
+
+        writeln!(
+            f,
+            "Mean shifted by more than {} ms ({}):",
+            self.threshold,
+            self.shifted.len()
+        )?;
+        for shift in self.shifted.iter() {
+            writeln!(
+                f,
+                "  {:<30}{:>6.1} -> {:<6.1} ({:+.1})  n: {} -> {}",
+                trigram_str(&shift.trigram),
+                shift.before.mean(),
+                shift.after.mean(),
+                shift.delta(),
+                shift.before.pop(),
+                shift.after.pop()
+            )?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Categories:")?;
+        for category in self.categories.iter() {
+            writeln!(
+                f,
+                "  {:<16}{:>6.1} -> {:<6.1} ({:+.1})  n: {} -> {}",
+                format!("{}:", category.name),
+                category.before.mean(),
+                category.after.mean(),
+                category.after.mean() - category.before.mean(),
+                category.before.pop(),
+                category.after.pop()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        keymap::KeyMap,
+        modifiers::ModifierPolicy,
+        parse_trigram,
+        projection::Projection,
+        sample::{Micros, Samples},
+        TrigramData,
+    };
+
+    fn data(trigrams: &[(&str, &[u32])]) -> TrigramData {
+        let mut data = TrigramData::default();
+        let source = data.add_source(None);
+        for (trigram, ms) in trigrams {
+            let values = ms.iter().map(|ms| Micros(ms * 1000)).collect();
+            data.insert(
+                parse_trigram(trigram).unwrap(),
+                Samples::new(source, values),
+            );
+        }
+        data
+    }
+
+    #[test]
+    fn shifts_beyond_the_threshold_and_category_deltas() {
+        let before = data(&[
+            ("KeyA,KeyS,KeyD", &[100, 100]),
+            ("KeyS,KeyD,KeyF", &[100]),
+            ("KeyA,KeyJ,KeyS", &[200]),
+            ("KeyQ,KeyQ,KeyQ", &[300]),
+        ]);
+        let after = data(&[
+            ("KeyA,KeyS,KeyD", &[150, 150]),
+            ("KeyS,KeyD,KeyF", &[90]),
+            ("KeyA,KeyJ,KeyS", &[170]),
+            ("KeyZ,KeyZ,KeyZ", &[50]),
+        ]);
+
+        let diff = before.diff(after, 20.0, TrigramData::matrix_3x10);
+        let trigram = |s: &str| parse_trigram(s).unwrap();
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].trigram, trigram("KeyZ,KeyZ,KeyZ"));
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].trigram, trigram("KeyQ,KeyQ,KeyQ"));
+        let shifted = diff
+            .shifted
+            .iter()
+            .map(|s| (s.trigram, s.delta()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shifted,
+            [
+                (trigram("KeyA,KeyS,KeyD"), 50.0),
+                (trigram("KeyA,KeyJ,KeyS"), -30.0)
+            ]
+        );
+
+        let category = |name: &str| {
+            let c = diff.categories.iter().find(|c| c.name == name).unwrap();
+            (
+                c.before.mean(),
+                c.after.mean(),
+                c.before.pop(),
+                c.after.pop(),
+            )
+        };
+        assert_eq!(category("Overall"), (160.0, 122.0, 5, 5));
+        assert_eq!(category("Sfr"), (300.0, 50.0, 1, 1));
+        assert_eq!(category("Alternate"), (200.0, 170.0, 1, 1));
+        assert!(diff
+            .to_string()
+            .contains("Mean shifted by more than 20 ms (2):"));
+    }
+
+    #[test]
+    fn categories_follow_the_projection() {
+        let before = data(&[("KeyA,KeyS,KeyD", &[100]), ("KeyS,KeyD,KeyF", &[100])]);
+        let after = data(&[("KeyA,KeyS,KeyD", &[80]), ("KeyS,KeyD,KeyF", &[120])]);
+
+        let without_a = |data: TrigramData| {
+            let mut keymap = KeyMap::preset("ansi").unwrap();
+            keymap.remove("KeyA".parse().unwrap());
+            let projection = Projection::default();
+            data.project(projection, keymap, ModifierPolicy::Drop)
+                .unwrap()
+                .0
+        };
+        let diff = before.diff(after, 20.0, without_a);
+
+        let overall = &diff.categories[0];
+        assert_eq!(overall.name, "Overall");
+        assert_eq!(
+            (overall.before.mean(), overall.after.mean()),
+            (100.0, 120.0)
+        );
+        assert_eq!(overall.after.pop(), 1);
+    }
+}
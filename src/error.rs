@@ -0,0 +1,208 @@
+use std::path::PathBuf;
+
+use crate::metadata::MetadataConflict;
+
+/// Error returned when a dataset can't be loaded. `path` is the file the error came from, or
+/// `None` when the data didn't come from a file.
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: Option<PathBuf>,
+    pub kind: LoadErrorKind,
+}
+
+#[derive(Debug)]
+pub enum LoadErrorKind {
+    Io(std::io::Error),
+    /// Invalid JSON, or JSON that doesn't have the shape of a dataset.
+    Json {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A trigram contains a key that isn't a known `KeyboardEvent.code`.
+    UnknownKey {
+        trigram: String,
+        key: String,
+    },
+    /// A trigram key with empty parts, like `"KeyA,,KeyB"`.
+    MalformedTrigram {
+        trigram: String,
+    },
+    /// A trigram key that doesn't consist of exactly three keys.
+    WrongArity {
+        trigram: String,
+        found: usize,
+    },
+    /// A CSV or TSV row that can't be read.
+    Table {
+        line: usize,
+        message: String,
+    },
+    /// A binary dataset that is truncated or otherwise invalid.
+    Binary(String),
+    UnsupportedVersion(u32),
+    MissingVersion,
+    Incompatible(MetadataConflict),
+    /// A key map file that doesn't describe a valid map.
+    KeyMap(String),
+    /// A projection file that doesn't describe a valid projection.
+    Projection(String),
+}
+
+impl LoadError {
+    pub fn new(path: Option<PathBuf>, kind: LoadErrorKind) -> Self {
+        Self { path, kind }
+    }
+
+    /// Converts a serde_json error, preferring the structured error stashed by the visitor if there
+    /// is one.
+    pub(crate) fn from_json(
+        path: Option<PathBuf>,
+        err: serde_json::Error,
+        stashed: Option<LoadErrorKind>,
+    ) -> Self {
+        let kind = match stashed {
+            Some(kind) => kind,
+            None if err.is_io() => LoadErrorKind::Io(err.into()),
+            None => LoadErrorKind::Json {
+                line: err.line(),
+                column: err.column(),
+                message: err.to_string(),
+            },
+        };
+
+        Self { path, kind }
+    }
+}
+
+impl std::fmt::Display for LoadErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use LoadErrorKind::*;
+
+        match self {
+            Io(e) => write!(f, "{e}"),
+            Json { message, .. } => write!(f, "{message}"),
+            UnknownKey { trigram, key } => write!(f, "unknown key '{key}' in trigram '{trigram}'"),
+            MalformedTrigram { trigram } => write!(f, "malformed trigram '{trigram}'"),
+            WrongArity { trigram, found } => {
+                write!(f, "trigram '{trigram}' has {found} keys instead of 3")
+            }
+            Table { line, message } => write!(f, "line {line}: {message}"),
+            Binary(message) => write!(f, "invalid binary dataset: {message}"),
+            UnsupportedVersion(v) => write!(
+                f,
+                "unsupported dataset version {v}, newest supported is {}",
+                crate::metadata::SCHEMA_VERSION
+            ),
+            MissingVersion => write!(f, "dataset has metadata but no version"),
+            Incompatible(conflict) => write!(f, "{conflict}"),
+            KeyMap(message) => write!(f, "invalid key map: {message}"),
+            Projection(message) => write!(f, "invalid projection: {message}"),
+        }
+    }
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}: {}", path.display(), self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            LoadErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        keymap::KeyMap, load::LoadMode, projection::Projection, testing::temp_path, TrigramData,
+    };
+
+    fn json_error(json: &str) -> LoadErrorKind {
+        TrigramData::from_json_str(json).unwrap_err().kind
+    }
+
+    #[test]
+    fn every_kind_is_reported_with_its_details() {
+        let missing = TrigramData::load("data/missing.json").unwrap_err();
+        assert!(matches!(missing.kind, LoadErrorKind::Io(_)));
+        assert!(missing.to_string().starts_with("data/missing.json: "));
+
+        let kind = json_error("{\n\"KeyA,KeyB,KeyC\": [90,");
+        assert!(
+            matches!(kind, LoadErrorKind::Json { line: 2, .. }),
+            "{kind:?}"
+        );
+
+        let kind = json_error(r#"{"KeyA,KeyB,Nope": [90]}"#);
+        assert_eq!(
+            kind.to_string(),
+            "unknown key 'Nope' in trigram 'KeyA,KeyB,Nope'"
+        );
+        let kind = json_error(r#"{"KeyA,,KeyC": [90]}"#);
+        assert_eq!(kind.to_string(), "malformed trigram 'KeyA,,KeyC'");
+        let kind = json_error(r#"{"KeyA,KeyB": [90]}"#);
+        assert_eq!(
+            kind.to_string(),
+            "trigram 'KeyA,KeyB' has 2 keys instead of 3"
+        );
+
+        let kind = json_error(r#"{"version": 2, "trigrams": {}}"#);
+        assert!(matches!(kind, LoadErrorKind::UnsupportedVersion(2)));
+        let kind = json_error(r#"{"metadata": {"layout": "dvorak"}, "trigrams": {}}"#);
+        assert!(matches!(kind, LoadErrorKind::MissingVersion));
+
+        let path = temp_path("error.csv");
+        std::fs::write(&path, "key1,key2,key3,duration\nKeyA,KeyB,KeyC,fast\n").unwrap();
+        let table = TrigramData::load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(table.kind, LoadErrorKind::Table { line: 2, .. }));
+
+        let mut data = TrigramData::default();
+        let binary = data
+            .read_into(&b"TTDB\x01"[..], LoadMode::Strict)
+            .unwrap_err();
+        assert!(matches!(binary.kind, LoadErrorKind::Binary(_)));
+
+        let envelope = |keyboard: &str| {
+            format!(
+                r#"{{"version": 1, "metadata": {{"keyboard": "{keyboard}"}}, "trigrams": {{}}}}"#
+            )
+        };
+        let paths = [temp_path("ansi.json"), temp_path("iso.json")];
+        std::fs::write(&paths[0], envelope("ansi")).unwrap();
+        std::fs::write(&paths[1], envelope("iso")).unwrap();
+        let incompatible = TrigramData::load_multiple(&paths).unwrap_err();
+        paths
+            .iter()
+            .for_each(|path| std::fs::remove_file(path).unwrap());
+        assert_eq!(incompatible.path.as_deref(), Some(paths[1].as_path()));
+        assert!(matches!(
+            incompatible.kind,
+            LoadErrorKind::Incompatible(MetadataConflict {
+                field: "keyboard",
+                ..
+            })
+        ));
+
+        let keymap = KeyMap::read(&br#"{"base": "qwertz"}"#[..]).unwrap_err();
+        assert_eq!(
+            keymap.to_string(),
+            "invalid key map: unknown base map 'qwertz'"
+        );
+        let projection = Projection::read(&br#"{"cells": [[7, 0]]}"#[..]).unwrap_err();
+        assert_eq!(
+            projection.to_string(),
+            "invalid projection: cell [7, 0] is outside the grid"
+        );
+    }
+}
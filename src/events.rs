@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{LoadError, LoadErrorKind},
+    mapping::Pos,
+    metadata::DatasetMetadata,
+    modifiers::{is_modifier, ModifierPolicy},
+    sample::{Micros, Samples},
+    TrigramData,
+};
+
+/// A single keypress as recorded by the browser, before it's reduced to n-gram durations.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyEvent {
+    /// `KeyboardEvent.code` of the key.
+    pub code: String,
+    /// `performance.now()` at keydown, in ms.
+    pub down: f64,
+    /// `performance.now()` at keyup, in ms, if it was recorded. Durations only use keydowns, so this
+    /// is optional and only kept to write logs back out unchanged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub up: Option<f64>,
+    /// Timestamps are only comparable within a session, usually a single page load.
+    pub session: String,
+}
+
+/// A raw keystroke log. On disk this is one [`KeyEvent`] JSON object per line, in any order:
+///
+/// ```json
+/// {"code": "KeyT", "down": 1520.3, "up": 1601.9, "session": "2023-04-01-a"}
+/// {"code": "KeyH", "down": 1588.1, "session": "2023-04-01-a"}
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventLog {
+    pub events: Vec<KeyEvent>,
+}
+
+impl EventLog {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        let path = path.as_ref();
+
+        File::open(path)
+            .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))
+            .and_then(|f| Self::read(BufReader::new(f)))
+            .map_err(|mut e| {
+                e.path = Some(path.to_path_buf());
+                e
+            })
+    }
+
+    pub fn read<R: Read>(reader: R) -> Result<Self, LoadError> {
+        let events = serde_json::Deserializer::from_reader(reader)
+            .into_iter::<KeyEvent>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| LoadError::from_json(None, e, None))?;
+
+        Ok(Self { events })
+    }
+
+    /// Writes the log in the same one event per line format it's read in.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        for event in self.events.iter() {
+            serde_json::to_writer(&mut w, event)?;
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+
+    /// Events grouped by session, in the order each session first appears. The events of every
+    /// session are sorted by keydown.
+    pub fn sessions(&self) -> Vec<(&str, Vec<&KeyEvent>)> {
+        let mut sessions: Vec<(&str, Vec<&KeyEvent>)> = Vec::new();
+        let mut indexes = HashMap::<&str, usize>::new();
+
+        for event in self.events.iter() {
+            let i = *indexes.entry(&event.session).or_insert_with(|| {
+                sessions.push((&event.session, Vec::new()));
+                sessions.len() - 1
+            });
+            sessions[i].1.push(event);
+        }
+
+        for (_, events) in sessions.iter_mut() {
+            events.sort_by(|a, b| a.down.total_cmp(&b.down));
+        }
+
+        sessions
+    }
+}
+
+/// N-gram durations keyed by the positions of their keys.
+pub type NgramData = HashMap<Vec<Pos>, Vec<Micros>>;
+
+/// Derives n-gram timings from an [`EventLog`] the same way the extension does while collecting:
+/// the duration of an n-gram is the time between the keydowns of its first and last key.
+#[derive(Copy, Clone, Debug)]
+pub struct Reconstructor {
+    /// N-grams that take this many ms or longer are dropped. The extension uses 750.
+    pub max_delay: Option<u16>,
+    /// With [`ModifierPolicy::Fold`] modifier keydowns are left out, so `Shift+X` is counted as
+    /// `X`, and the trigrams of keys typed while a modifier was held are recorded as folded.
+    /// Otherwise modifiers are part of n-grams like any other key.
+    pub modifiers: ModifierPolicy,
+}
+
+impl Default for Reconstructor {
+    fn default() -> Self {
+        Self {
+            max_delay: Some(750),
+            modifiers: ModifierPolicy::default(),
+        }
+    }
+}
+
+impl Reconstructor {
+    /// Calls `f` with every n-gram of length `n` that fits within `max_delay`, and whether a
+    /// modifier was folded into any of its keys. N-grams never span sessions, and any n-gram
+    /// containing a key code that can't be parsed is skipped.
+    fn for_each_ngram(
+        &self,
+        n: usize,
+        events: &[&KeyEvent],
+        mut f: impl FnMut(Vec<Pos>, bool, Micros),
+    ) {
+        if n == 0 {
+            return;
+        }
+
+        let mut events = events
+            .iter()
+            .map(|e| (*e, e.code.parse::<Pos>().ok(), false))
+            .collect::<Vec<_>>();
+
+        if self.modifiers == ModifierPolicy::Fold {
+            let mut held = Vec::<&KeyEvent>::new();
+            events.retain_mut(|(event, pos, folded)| {
+                if pos.is_some_and(is_modifier) {
+                    held.push(event);
+                    return false;
+                }
+                // Without a keyup, a modifier is taken to be held for the next key only.
+                *folded = held.iter().any(|m| m.up.is_none_or(|up| up > event.down));
+                held.retain(|m| m.up.is_some_and(|up| up > event.down));
+                true
+            });
+        }
+
+        for window in events.windows(n) {
+            let duration = window[n - 1].0.down - window[0].0.down;
+
+            if matches!(self.max_delay, Some(max) if duration >= max as f64) {
+                continue;
+            }
+
+            let (Some(poss), Some(duration)) = (
+                window
+                    .iter()
+                    .map(|(_, pos, _)| *pos)
+                    .collect::<Option<Vec<_>>>(),
+                Micros::from_ms(duration),
+            ) else {
+                continue;
+            };
+
+            f(poss, window.iter().any(|(.., folded)| *folded), duration);
+        }
+    }
+
+    pub fn ngrams(&self, n: usize, log: &EventLog) -> NgramData {
+        let mut data = NgramData::new();
+
+        for (_, events) in log.sessions() {
+            self.for_each_ngram(n, &events, |ngram, _, duration| {
+                data.entry(ngram).or_default().push(duration)
+            });
+        }
+
+        data
+    }
+
+    /// Reconstructs trigram data with every session as a separate source.
+    pub fn trigrams(&self, log: &EventLog) -> TrigramData {
+        let mut data = TrigramData {
+            metadata: DatasetMetadata {
+                max_delay: self.max_delay,
+                ..Default::default()
+            },
+            folded: (self.modifiers == ModifierPolicy::Fold).then(HashMap::new),
+            ..Default::default()
+        };
+
+        for (session, events) in log.sessions() {
+            let source = data.add_source(Some(session.to_string()));
+            data.sources[source as usize].metadata = data.metadata.clone();
+
+            let mut trigrams = HashMap::<[Pos; 3], Vec<Micros>>::new();
+            self.for_each_ngram(3, &events, |ngram, folded, duration| {
+                let trigram = [ngram[0], ngram[1], ngram[2]];
+                trigrams.entry(trigram).or_default().push(duration);
+                if let (true, Some(counts)) = (folded, data.folded.as_mut()) {
+                    *counts.entry(trigram).or_default() += 1;
+                }
+            });
+
+            for (trigram, values) in trigrams {
+                data.insert(trigram, Samples::new(source, values));
+            }
+        }
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::shifted;
+
+    #[test]
+    fn sessions_are_grouped_and_sorted() {
+        let log = EventLog::read(
+            r#"{"code": "KeyC", "down": 300, "session": "a"}
+{"code": "KeyX", "down": 5, "session": "b"}
+{"code": "KeyA", "down": 100, "up": 150, "session": "a"}
+{"code": "KeyB", "down": 180, "session": "a"}"#
+                .as_bytes(),
+        )
+        .unwrap();
+
+        let sessions = log.sessions();
+        let codes =
+            |events: &[&KeyEvent]| events.iter().map(|e| e.code.clone()).collect::<Vec<_>>();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].0, "a");
+        assert_eq!(codes(&sessions[0].1), ["KeyA", "KeyB", "KeyC"]);
+        assert_eq!(codes(&sessions[1].1), ["KeyX"]);
+
+        let data = Reconstructor::default().trigrams(&log);
+        let trigram = crate::parse_trigram("KeyA,KeyB,KeyC").unwrap();
+        assert_eq!(data.trigrams[&trigram].values(), [Micros(200_000)]);
+        assert_eq!(data.sample_count(), 1);
+    }
+
+    #[test]
+    fn modifiers_are_folded_into_the_keys_they_modify() {
+        let trigram = |t| crate::parse_trigram(t).unwrap();
+        let durations = |data: &TrigramData| {
+            let mut durations = data
+                .trigrams
+                .iter()
+                .map(|(t, s)| (crate::mapping::trigram_str(t), s.values()[0].0 / 1000))
+                .collect::<Vec<_>>();
+            durations.sort();
+            durations
+        };
+
+        let folded = shifted(ModifierPolicy::Fold);
+        assert_eq!(
+            durations(&folded),
+            [
+                ("KeyA,KeyB,KeyC".to_string(), 110),
+                ("KeyB,KeyC,KeyD".to_string(), 130),
+                ("KeyC,KeyD,KeyE".to_string(), 120),
+            ]
+        );
+        let counts = folded.folded.as_ref().unwrap();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&trigram("KeyA,KeyB,KeyC")], 1);
+        assert_eq!(counts[&trigram("KeyC,KeyD,KeyE")], 1);
+
+        for modifiers in [ModifierPolicy::Drop, ModifierPolicy::Separate] {
+            let data = shifted(modifiers);
+            assert!(data.folded.is_none());
+            assert_eq!(
+                durations(&data),
+                [
+                    ("KeyA,KeyB,KeyC".to_string(), 110),
+                    ("KeyB,KeyC,KeyD".to_string(), 130),
+                    ("KeyC,KeyD,ShiftRight".to_string(), 80),
+                    ("KeyD,ShiftRight,KeyE".to_string(), 50),
+                    ("ShiftLeft,KeyA,KeyB".to_string(), 100),
+                ]
+            );
+        }
+    }
+}
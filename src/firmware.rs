@@ -0,0 +1,566 @@
+//! Building [`KeyMap`]s from QMK, VIA and ZMK keymaps, for typists who remap in firmware.
+//!
+//! Only the base layer is used. Its keys are taken in the order they're listed, as rows of
+//! `columns` keys. The letter block is the middle ten columns of the first three rows after
+//! `skip_rows`, with the columns next to it as the outer pinky columns and the row before it as
+//! the number row. The keys after the letter rows, up to one row of them, are the thumb row, which
+//! is centred on row 3 of the key map so that the inner thumb keys end up in columns 4 and 5.
+//!
+//! VIA exports list their keys in the order of the electrical matrix rather than by position, so
+//! they're read together with the keyboard's VIA definition. Its `layouts.keymap` is a KLE layout
+//! whose keys are labelled with their `"row,col"` in the matrix, which puts the exported keys in
+//! rows by where they sit on the board before they're read like any other keymap.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{LoadError, LoadErrorKind},
+    keymap::{KeyMap, MappedKey},
+    mapping::Pos,
+};
+
+/// How the keys of a firmware keymap are laid out. Fields left at `None` are guessed from the QMK
+/// layout name or the number of keys.
+#[derive(Clone, Debug, Default)]
+pub struct ImportOptions {
+    /// Keys per row, including outer columns.
+    pub columns: Option<usize>,
+    /// Rows before the top letter row, like a number row.
+    pub skip_rows: Option<usize>,
+    /// The VIA definition of the keyboard, which VIA exports are read with.
+    pub via_definition: Option<PathBuf>,
+}
+
+/// QMK keycode names and their ZMK equivalents, by `KeyboardEvent.code`.
+const KEYCODES: &[(&str, &[&str])] = &[
+    ("KeyA", &["KC_A", "A"]),
+    ("KeyB", &["KC_B", "B"]),
+    ("KeyC", &["KC_C", "C"]),
+    ("KeyD", &["KC_D", "D"]),
+    ("KeyE", &["KC_E", "E"]),
+    ("KeyF", &["KC_F", "F"]),
+    ("KeyG", &["KC_G", "G"]),
+    ("KeyH", &["KC_H", "H"]),
+    ("KeyI", &["KC_I", "I"]),
+    ("KeyJ", &["KC_J", "J"]),
+    ("KeyK", &["KC_K", "K"]),
+    ("KeyL", &["KC_L", "L"]),
+    ("KeyM", &["KC_M", "M"]),
+    ("KeyN", &["KC_N", "N"]),
+    ("KeyO", &["KC_O", "O"]),
+    ("KeyP", &["KC_P", "P"]),
+    ("KeyQ", &["KC_Q", "Q"]),
+    ("KeyR", &["KC_R", "R"]),
+    ("KeyS", &["KC_S", "S"]),
+    ("KeyT", &["KC_T", "T"]),
+    ("KeyU", &["KC_U", "U"]),
+    ("KeyV", &["KC_V", "V"]),
+    ("KeyW", &["KC_W", "W"]),
+    ("KeyX", &["KC_X", "X"]),
+    ("KeyY", &["KC_Y", "Y"]),
+    ("KeyZ", &["KC_Z", "Z"]),
+    ("Digit1", &["KC_1", "N1", "NUMBER_1"]),
+    ("Digit2", &["KC_2", "N2", "NUMBER_2"]),
+    ("Digit3", &["KC_3", "N3", "NUMBER_3"]),
+    ("Digit4", &["KC_4", "N4", "NUMBER_4"]),
+    ("Digit5", &["KC_5", "N5", "NUMBER_5"]),
+    ("Digit6", &["KC_6", "N6", "NUMBER_6"]),
+    ("Digit7", &["KC_7", "N7", "NUMBER_7"]),
+    ("Digit8", &["KC_8", "N8", "NUMBER_8"]),
+    ("Digit9", &["KC_9", "N9", "NUMBER_9"]),
+    ("Digit0", &["KC_0", "N0", "NUMBER_0"]),
+    ("Semicolon", &["KC_SCLN", "KC_SCOLON", "SEMI", "SEMICOLON"]),
+    (
+        "Quote",
+        &[
+            "KC_QUOT",
+            "KC_QUOTE",
+            "SQT",
+            "APOS",
+            "APOSTROPHE",
+            "SINGLE_QUOTE",
+        ],
+    ),
+    ("Comma", &["KC_COMM", "KC_COMMA", "COMMA"]),
+    ("Period", &["KC_DOT", "DOT", "PERIOD"]),
+    ("Slash", &["KC_SLSH", "KC_SLASH", "FSLH", "SLASH"]),
+    ("Minus", &["KC_MINS", "KC_MINUS", "MINUS"]),
+    ("Equal", &["KC_EQL", "KC_EQUAL", "EQUAL"]),
+    (
+        "BracketLeft",
+        &["KC_LBRC", "KC_LBRACKET", "LBKT", "LEFT_BRACKET"],
+    ),
+    (
+        "BracketRight",
+        &["KC_RBRC", "KC_RBRACKET", "RBKT", "RIGHT_BRACKET"],
+    ),
+    ("Backslash", &["KC_BSLS", "KC_BSLASH", "BSLH", "BACKSLASH"]),
+    ("Backquote", &["KC_GRV", "KC_GRAVE", "GRAVE"]),
+    ("IntlBackslash", &["KC_NUBS", "NON_US_BSLH"]),
+    ("IntlRo", &["KC_INT1", "KC_RO", "INT1", "INT_RO"]),
+    ("IntlYen", &["KC_INT3", "KC_JYEN", "INT3", "INT_YEN"]),
+    ("Space", &["KC_SPC", "KC_SPACE", "SPACE", "SPC"]),
+    ("Enter", &["KC_ENT", "KC_ENTER", "RET", "ENTER", "RETURN"]),
+    (
+        "Backspace",
+        &["KC_BSPC", "KC_BACKSPACE", "BSPC", "BACKSPACE"],
+    ),
+    ("Tab", &["KC_TAB", "TAB"]),
+    ("Escape", &["KC_ESC", "KC_ESCAPE", "ESC", "ESCAPE"]),
+];
+
+/// The code a QMK or ZMK key binding sends when tapped. Mod-taps, layer-taps and modifier
+/// wrappers like `LSFT_T(KC_A)`, `LT(1, KC_SPC)` or `&mt LSHIFT A` send their last argument.
+fn binding_code(binding: &str) -> Option<Pos> {
+    let key = binding
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .rfind(|token| !token.is_empty())?;
+
+    KEYCODES
+        .iter()
+        .find(|(_, names)| names.contains(&key))
+        .and_then(|(code, _)| code.parse().ok())
+}
+
+/// Guesses `(columns, skip_rows)` from a QMK layout name like `LAYOUT_split_3x6_3`, or else from
+/// the number of keys.
+fn guess_shape(layout: Option<&str>, keys: usize) -> Option<(usize, usize)> {
+    let layout = layout.unwrap_or_default().to_ascii_lowercase();
+
+    if layout.contains("3x5") {
+        Some((10, 0))
+    } else if layout.contains("3x6") || layout.contains("4x12") || layout.contains("planck") {
+        Some((12, 0))
+    } else if layout.contains("5x12") {
+        Some((12, 1))
+    } else {
+        match keys {
+            34..=38 => Some((10, 0)),
+            40..=48 => Some((12, 0)),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> LoadError {
+    LoadError::new(None, LoadErrorKind::KeyMap(message.into()))
+}
+
+/// Builds a map from the codes sent by the base layer, see the [module docs](self).
+fn from_layer(
+    name: String,
+    layout: Option<&str>,
+    layer: &[Option<Pos>],
+    options: &ImportOptions,
+) -> Result<KeyMap, LoadError> {
+    let guess = guess_shape(layout, layer.len());
+    let columns = options.columns.or(guess.map(|(columns, _)| columns));
+    let skip_rows = options.skip_rows.or(guess.map(|(_, skip)| skip));
+
+    let (Some(columns), Some(skip_rows)) = (columns, skip_rows) else {
+        return Err(invalid(format!(
+            "can't tell the layout of a {} key base layer, specify the columns",
+            layer.len()
+        )));
+    };
+    if columns < 10 || columns % 2 != 0 {
+        return Err(invalid(format!(
+            "{columns} columns don't have a 3x10 block in the middle"
+        )));
+    }
+
+    let offset = (columns - 10) as isize / 2;
+    let (skip_rows, columns) = (skip_rows as isize, columns as isize);
+    let mut map = KeyMap::new(name);
+
+    let top = match skip_rows {
+        0 => 0,
+        _ => -1,
+    };
+    let outer = offset.min(1);
+    let mut insert = |i: isize, row: isize, col: isize| {
+        if let Some(Some(pos)) = layer.get(i as usize) {
+            if map.get(*pos).is_none() {
+                map.insert(*pos, MappedKey::new(row, col));
+            }
+        }
+    };
+
+    for row in top..3 {
+        for col in -outer..10 + outer {
+            insert((skip_rows + row) * columns + offset + col, row, col);
+        }
+    }
+
+    let thumbs_start = (skip_rows + 3) * columns;
+    let thumbs = (layer.len() as isize - thumbs_start).clamp(0, columns);
+    let first_col = 5 - (thumbs + 1) / 2;
+    for i in 0..thumbs {
+        insert(thumbs_start + i, 3, first_col + i);
+    }
+
+    Ok(map)
+}
+
+/// A QMK `keymap.json` or a VIA layout export. Both list layers as arrays of keycodes, but only
+/// VIA exports have a `vendorProductId`.
+#[derive(Deserialize)]
+struct QmkKeymap {
+    #[serde(alias = "name")]
+    keymap: Option<String>,
+    layout: Option<String>,
+    layers: Vec<Vec<serde_json::Value>>,
+    #[serde(rename = "vendorProductId")]
+    vendor_product_id: Option<serde_json::Value>,
+}
+
+impl QmkKeymap {
+    fn parse(json: &str) -> Result<Self, LoadError> {
+        serde_json::from_str(json).map_err(|e| LoadError::from_json(None, e, None))
+    }
+
+    /// The codes of the base layer, in the order they're listed.
+    fn base_layer(&self) -> Result<Vec<Option<Pos>>, LoadError> {
+        let Some(layer) = self.layers.first() else {
+            return Err(invalid("keymap has no layers"));
+        };
+
+        Ok(layer
+            .iter()
+            .map(|key| key.as_str().and_then(binding_code))
+            .collect())
+    }
+}
+
+/// The parts of a VIA keyboard definition needed to put the keys of an export in place.
+#[derive(Deserialize)]
+struct ViaDefinition {
+    matrix: ViaMatrix,
+    layouts: ViaLayouts,
+}
+
+impl ViaDefinition {
+    fn load(path: &Path) -> Result<Self, LoadError> {
+        let with_path = |kind| LoadError::new(Some(path.to_path_buf()), kind);
+        let file = fs::File::open(path).map_err(|e| with_path(LoadErrorKind::Io(e)))?;
+
+        serde_json::from_reader(std::io::BufReader::new(file))
+            .map_err(|e| LoadError::from_json(Some(path.to_path_buf()), e, None))
+    }
+}
+
+#[derive(Deserialize)]
+struct ViaMatrix {
+    cols: usize,
+}
+
+#[derive(Deserialize)]
+struct ViaLayouts {
+    keymap: Vec<Vec<serde_json::Value>>,
+}
+
+/// The matrix positions of the keys of a KLE layout, as rows of `(row, col)` from left to right.
+/// Decals and keys of any but the first choice of a layout option are skipped, and rotation is
+/// ignored.
+fn kle_rows(layout: &[Vec<serde_json::Value>]) -> Result<Vec<Vec<(usize, usize)>>, LoadError> {
+    let (mut x, mut y, mut rx) = (0.0, 0.0, 0.0);
+    let mut keys = Vec::new();
+
+    for row in layout {
+        let (mut width, mut decal) = (1.0, false);
+
+        for item in row {
+            match item {
+                serde_json::Value::Object(props) => {
+                    let prop = |name| props.get(name).and_then(serde_json::Value::as_f64);
+                    if let Some(v) = prop("rx") {
+                        (rx, x) = (v, v);
+                    }
+                    if let Some(v) = prop("ry") {
+                        y = v;
+                    }
+                    x += prop("x").unwrap_or_default();
+                    y += prop("y").unwrap_or_default();
+                    width = prop("w").unwrap_or(width);
+                    decal = props.get("d").and_then(|d| d.as_bool()).unwrap_or(decal);
+                }
+                serde_json::Value::String(label) => {
+                    let mut legends = label.split('\n');
+                    let matrix = legends.next().unwrap_or_default();
+                    let first_choice = legends
+                        .nth(2)
+                        .and_then(|option| option.split_once(','))
+                        .is_none_or(|(_, choice)| choice.trim() == "0");
+
+                    if !decal && first_choice {
+                        let Some((row, col)) = matrix.split_once(',').and_then(|(r, c)| {
+                            Some((r.trim().parse().ok()?, c.trim().parse().ok()?))
+                        }) else {
+                            return Err(invalid(format!(
+                                "VIA layout key '{matrix}' isn't labelled with its row,col"
+                            )));
+                        };
+                        keys.push((y, x + width / 2.0, (row, col)));
+                    }
+                    x += width;
+                    (width, decal) = (1.0, false);
+                }
+                _ => {}
+            }
+        }
+
+        y += 1.0;
+        x = rx;
+    }
+
+    keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut rows = Vec::<Vec<(f64, f64, (usize, usize))>>::new();
+    for key in keys {
+        match rows.last_mut() {
+            Some(row) if key.0 - row[0].0 < 0.5 => row.push(key),
+            _ => rows.push(vec![key]),
+        }
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|mut row| {
+            row.sort_by(|a, b| a.1.total_cmp(&b.1));
+            row.into_iter().map(|(.., pos)| pos).collect()
+        })
+        .collect())
+}
+
+/// Puts the base layer of a VIA `export` in rows with `definition`, see [`KeyMap::from_via`].
+fn via_layer(
+    export: &str,
+    definition: &ViaDefinition,
+    options: &ImportOptions,
+) -> Result<KeyMap, LoadError> {
+    let keymap = QmkKeymap::parse(export)?;
+    let matrix = keymap.base_layer()?;
+
+    let mut layer = Vec::with_capacity(matrix.len());
+    for (row, col) in kle_rows(&definition.layouts.keymap)?.into_iter().flatten() {
+        match matrix.get(row * definition.matrix.cols + col) {
+            Some(code) => layer.push(*code),
+            None => {
+                return Err(invalid(format!(
+                    "VIA layout key {row},{col} isn't in the exported layer"
+                )))
+            }
+        }
+    }
+
+    from_layer(keymap.keymap.unwrap_or_default(), None, &layer, options)
+}
+
+/// Whether `json` looks like a QMK or VIA keymap rather than a key map file of our own.
+fn is_qmk(json: &serde_json::Value) -> bool {
+    json.get("layers").is_some_and(|layers| layers.is_array())
+}
+
+impl KeyMap {
+    /// Reads a QMK `keymap.json`. VIA exports are refused, since they need their definition, see
+    /// [`KeyMap::from_via`].
+    pub fn from_qmk(json: &str, options: &ImportOptions) -> Result<Self, LoadError> {
+        let keymap = QmkKeymap::parse(json)?;
+
+        if keymap.vendor_product_id.is_some() {
+            return Err(invalid(
+                "VIA exports list keys in matrix order, pass the keyboard's VIA definition to put \
+                 them in place",
+            ));
+        }
+
+        let layer = keymap.base_layer()?;
+        from_layer(
+            keymap.keymap.unwrap_or_default(),
+            keymap.layout.as_deref(),
+            &layer,
+            options,
+        )
+    }
+
+    /// Reads a VIA layout export, whose keys are put in rows by the KLE layout of the keyboard's
+    /// VIA `definition`, see the [module docs](self). The rows should be as long as each other,
+    /// as on the split and ortho boards this is meant for.
+    pub fn from_via(
+        export: &str,
+        definition: &str,
+        options: &ImportOptions,
+    ) -> Result<Self, LoadError> {
+        let definition =
+            serde_json::from_str(definition).map_err(|e| LoadError::from_json(None, e, None))?;
+        via_layer(export, &definition, options)
+    }
+
+    /// Reads a ZMK devicetree keymap, using the first layer of its `zmk,keymap` node.
+    pub fn from_zmk(source: &str, options: &ImportOptions) -> Result<Self, LoadError> {
+        let source = strip_comments(source);
+
+        let bindings = source
+            .find("\"zmk,keymap\"")
+            .map(|start| &source[start..])
+            .and_then(|keymap| keymap.split_once("bindings"))
+            .and_then(|(_, rest)| rest.split_once('<'))
+            .and_then(|(_, rest)| rest.split_once('>'))
+            .map(|(bindings, _)| bindings)
+            .ok_or_else(|| invalid("no layer bindings in a zmk,keymap node"))?;
+
+        let layer = bindings
+            .split('&')
+            .map(str::trim)
+            .filter(|binding| !binding.is_empty())
+            .map(|binding| match binding.split_whitespace().count() {
+                1 => None,
+                _ => binding_code(binding),
+            })
+            .collect::<Vec<_>>();
+
+        from_layer(String::new(), None, &layer, options)
+    }
+
+    /// Like [`KeyMap::load`], but also reads QMK and ZMK keymaps and VIA exports, which are laid
+    /// out according to `options`. ZMK keymaps are recognised by their `.keymap` extension, VIA
+    /// exports by their `vendorProductId`, and those are read with `options.via_definition`.
+    pub fn load_with<P: AsRef<Path>>(
+        name_or_path: P,
+        options: &ImportOptions,
+    ) -> Result<Self, LoadError> {
+        let path = name_or_path.as_ref();
+        let is_zmk = path.extension().is_some_and(|e| e == "keymap");
+        let is_json = path.extension().is_some_and(|e| e == "json");
+
+        if !is_zmk && !is_json {
+            return Self::load(path);
+        }
+
+        let with_path = |mut e: LoadError| {
+            e.path.get_or_insert_with(|| path.to_path_buf());
+            e
+        };
+        let source = fs::read_to_string(path)
+            .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))
+            .map_err(with_path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut map = match is_zmk {
+            true => Self::from_zmk(&source, options),
+            false => match serde_json::from_str(&source) {
+                Ok(json) if is_qmk(&json) => match &options.via_definition {
+                    Some(definition) if json.get("vendorProductId").is_some() => {
+                        ViaDefinition::load(definition)
+                            .and_then(|definition| via_layer(&source, &definition, options))
+                    }
+                    _ => Self::from_qmk(&source, options),
+                },
+                _ => Self::read(source.as_bytes()),
+            },
+        }
+        .map_err(with_path)?;
+
+        if map.name.is_empty() {
+            map.name = name;
+        }
+        Ok(map)
+    }
+}
+
+/// Removes `//` and `/* */` comments from devicetree source.
+fn strip_comments(source: &str) -> String {
+    let mut res = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(i) = rest.find("//").into_iter().chain(rest.find("/*")).min() {
+        res.push_str(&rest[..i]);
+        rest = match rest[i..].starts_with("//") {
+            true => rest[i..].find('\n').map_or("", |end| &rest[i + end..]),
+            false => rest[i..].find("*/").map_or("", |end| &rest[i + end + 2..]),
+        };
+    }
+    res.push_str(rest);
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURES: &str = "tests/fixtures/corne";
+
+    fn cell(map: &KeyMap, code: &str) -> Option<(isize, isize)> {
+        map.get(code.parse().unwrap()).map(|key| key.cell())
+    }
+
+    /// The base layer of the default Corne keymap, which both fixtures share.
+    fn assert_corne(map: &KeyMap) {
+        assert_eq!(cell(map, "KeyQ"), Some((0, 0)));
+        assert_eq!(cell(map, "KeyP"), Some((0, 9)));
+        assert_eq!(cell(map, "Semicolon"), Some((1, 9)));
+        assert_eq!(cell(map, "KeyZ"), Some((2, 0)));
+        assert_eq!(cell(map, "Slash"), Some((2, 9)));
+        assert_eq!(cell(map, "Tab"), Some((0, -1)));
+        assert_eq!(cell(map, "Quote"), Some((1, 10)));
+        assert_eq!(cell(map, "Escape"), Some((2, 10)));
+        assert_eq!(cell(map, "Space"), Some((3, 4)));
+        assert_eq!(cell(map, "Enter"), Some((3, 5)));
+        assert_eq!(cell(map, "ShiftLeft"), None);
+    }
+
+    #[test]
+    fn corne_keymaps_are_imported() {
+        let options = ImportOptions::default();
+
+        let qmk = KeyMap::load_with(format!("{FIXTURES}/keymap.json"), &options).unwrap();
+        assert_eq!(qmk.name, "default");
+        assert_corne(&qmk);
+
+        let zmk = KeyMap::load_with(format!("{FIXTURES}/corne.keymap"), &options).unwrap();
+        assert_eq!(zmk.name, "corne");
+        assert_corne(&zmk);
+
+        let via = KeyMap::load_with(format!("{FIXTURES}/via.json"), &options);
+        assert!(matches!(
+            via.unwrap_err().kind,
+            LoadErrorKind::KeyMap(message) if message.contains("matrix order")
+        ));
+
+        let options = ImportOptions {
+            via_definition: Some(format!("{FIXTURES}/via_definition.json").into()),
+            ..Default::default()
+        };
+        let via = KeyMap::load_with(format!("{FIXTURES}/via.json"), &options).unwrap();
+        assert_eq!(via.name, "Corne");
+        assert_corne(&via);
+    }
+
+    #[test]
+    fn via_layouts_are_read_by_position() {
+        let layout = r#"[
+            [{"x": 1}, "0,1", {"w": 2}, "0,2\n\n\n0,0", {"x": -2, "w": 2}, "0,3\n\n\n0,1"],
+            [{"y": -0.75, "d": true}, "", {"x": -2}, "0,0"],
+            [{"y": 0.25}, "1,0", "1,1"]
+        ]"#;
+        let layout = serde_json::from_str::<Vec<Vec<serde_json::Value>>>(layout).unwrap();
+        assert_eq!(
+            kle_rows(&layout).unwrap(),
+            [vec![(0, 0), (0, 1), (0, 2)], vec![(1, 0), (1, 1)]]
+        );
+
+        let unlabelled = serde_json::from_str::<Vec<Vec<serde_json::Value>>>(r#"[["A"]]"#);
+        assert!(matches!(
+            kle_rows(&unlabelled.unwrap()).unwrap_err().kind,
+            LoadErrorKind::KeyMap(message) if message.contains("row,col")
+        ));
+    }
+}
@@ -0,0 +1,443 @@
+//! Physical key geometry, so that metrics can use where keys actually are rather than their
+//! [`Pos`] grid index.
+//!
+//! Coordinates are in key units (1u is the width of a letter key), with x growing to the right and
+//! y growing downwards. The origin is the top left corner of the number row.
+
+use std::str::FromStr;
+
+use crate::mapping::Pos;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Board {
+    #[default]
+    Ansi,
+    Iso,
+    Jis,
+    /// A grid without stagger, like a Planck with the usual 3x10 letter block.
+    Ortho,
+    /// A column staggered split, like a Corne, with the halves 2u apart. Only the number row, the
+    /// letter block and Space on the left thumb are placed.
+    Colstag,
+}
+
+impl FromStr for Board {
+    type Err = String;
+
+    /// Parses the names used for the `keyboard` metadata field.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ansi" => Ok(Self::Ansi),
+            "iso" => Ok(Self::Iso),
+            "jis" => Ok(Self::Jis),
+            "ortho" | "ortholinear" => Ok(Self::Ortho),
+            "colstag" | "column-staggered" => Ok(Self::Colstag),
+            _ => Err(format!("unknown keyboard '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for Board {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Ansi => "ansi",
+            Self::Iso => "iso",
+            Self::Jis => "jis",
+            Self::Ortho => "ortho",
+            Self::Colstag => "colstag",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Where a key is on a [`Board`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KeyGeometry {
+    /// Centre of the key.
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    /// How far the key's row is shifted to the right compared to the number row, measured at its
+    /// first letter key. Zero on boards without row stagger.
+    pub row_stagger: f64,
+    /// How far the key's column is shifted down on column staggered boards.
+    pub column_stagger: f64,
+}
+
+impl KeyGeometry {
+    /// Distance between the centres of two keys, in key units.
+    pub fn distance(&self, other: &KeyGeometry) -> f64 {
+        (other.x - self.x).hypot(other.y - self.y)
+    }
+
+    /// Direction from this key to `other` in radians, 0 pointing right and π/2 pointing down.
+    pub fn direction(&self, other: &KeyGeometry) -> f64 {
+        (other.y - self.y).atan2(other.x - self.x)
+    }
+}
+
+/// A row of keys as `(code, width)` from left to right. An empty code leaves a gap.
+type Row = &'static [(&'static str, f64)];
+
+const LETTERS_TOP: Row = &[
+    ("KeyQ", 1.0),
+    ("KeyW", 1.0),
+    ("KeyE", 1.0),
+    ("KeyR", 1.0),
+    ("KeyT", 1.0),
+    ("KeyY", 1.0),
+    ("KeyU", 1.0),
+    ("KeyI", 1.0),
+    ("KeyO", 1.0),
+    ("KeyP", 1.0),
+];
+
+const LETTERS_HOME: Row = &[
+    ("KeyA", 1.0),
+    ("KeyS", 1.0),
+    ("KeyD", 1.0),
+    ("KeyF", 1.0),
+    ("KeyG", 1.0),
+    ("KeyH", 1.0),
+    ("KeyJ", 1.0),
+    ("KeyK", 1.0),
+    ("KeyL", 1.0),
+    ("Semicolon", 1.0),
+];
+
+const LETTERS_BOTTOM: Row = &[
+    ("KeyZ", 1.0),
+    ("KeyX", 1.0),
+    ("KeyC", 1.0),
+    ("KeyV", 1.0),
+    ("KeyB", 1.0),
+    ("KeyN", 1.0),
+    ("KeyM", 1.0),
+    ("Comma", 1.0),
+    ("Period", 1.0),
+    ("Slash", 1.0),
+];
+
+const DIGITS: Row = &[
+    ("Digit1", 1.0),
+    ("Digit2", 1.0),
+    ("Digit3", 1.0),
+    ("Digit4", 1.0),
+    ("Digit5", 1.0),
+    ("Digit6", 1.0),
+    ("Digit7", 1.0),
+    ("Digit8", 1.0),
+    ("Digit9", 1.0),
+    ("Digit0", 1.0),
+];
+
+const FUNCTION_ROW: Row = &[
+    ("Escape", 1.0),
+    ("", 1.0),
+    ("F1", 1.0),
+    ("F2", 1.0),
+    ("F3", 1.0),
+    ("F4", 1.0),
+    ("", 0.5),
+    ("F5", 1.0),
+    ("F6", 1.0),
+    ("F7", 1.0),
+    ("F8", 1.0),
+    ("", 0.5),
+    ("F9", 1.0),
+    ("F10", 1.0),
+    ("F11", 1.0),
+    ("F12", 1.0),
+];
+
+/// The function row sits half a key above the number row on row staggered boards.
+const FUNCTION_ROW_Y: f64 = -1.0;
+
+/// Rows of a row staggered board from the number row down, each split into the keys left of the
+/// letters, the letters, and the keys right of them.
+type Layout = [(Row, Row, Row); 5];
+
+const ANSI: Layout = [
+    (
+        &[("Backquote", 1.0)],
+        DIGITS,
+        &[("Minus", 1.0), ("Equal", 1.0), ("Backspace", 2.0)],
+    ),
+    (
+        &[("Tab", 1.5)],
+        LETTERS_TOP,
+        &[
+            ("BracketLeft", 1.0),
+            ("BracketRight", 1.0),
+            ("Backslash", 1.5),
+        ],
+    ),
+    (
+        &[("CapsLock", 1.75)],
+        LETTERS_HOME,
+        &[("Quote", 1.0), ("Enter", 2.25)],
+    ),
+    (
+        &[("ShiftLeft", 2.25)],
+        LETTERS_BOTTOM,
+        &[("ShiftRight", 2.75)],
+    ),
+    (
+        &[
+            ("ControlLeft", 1.25),
+            ("MetaLeft", 1.25),
+            ("AltLeft", 1.25),
+            ("Space", 6.25),
+        ],
+        &[],
+        &[
+            ("AltRight", 1.25),
+            ("MetaRight", 1.25),
+            ("ContextMenu", 1.25),
+            ("ControlRight", 1.25),
+        ],
+    ),
+];
+
+/// The ISO Enter spans two rows; only its upper part is listed and its centre moved down.
+const ISO: Layout = [
+    ANSI[0],
+    (
+        &[("Tab", 1.5)],
+        LETTERS_TOP,
+        &[("BracketLeft", 1.0), ("BracketRight", 1.0), ("Enter", 1.5)],
+    ),
+    (
+        &[("CapsLock", 1.75)],
+        LETTERS_HOME,
+        &[("Quote", 1.0), ("Backslash", 1.0)],
+    ),
+    (
+        &[("ShiftLeft", 1.25), ("IntlBackslash", 1.0)],
+        LETTERS_BOTTOM,
+        &[("ShiftRight", 2.75)],
+    ),
+    ANSI[4],
+];
+
+const JIS: Layout = [
+    (
+        &[("Backquote", 1.0)],
+        DIGITS,
+        &[
+            ("Minus", 1.0),
+            ("Equal", 1.0),
+            ("IntlYen", 1.0),
+            ("Backspace", 1.0),
+        ],
+    ),
+    ISO[1],
+    ISO[2],
+    (
+        &[("ShiftLeft", 2.25)],
+        LETTERS_BOTTOM,
+        &[("IntlRo", 1.0), ("ShiftRight", 1.75)],
+    ),
+    (
+        &[
+            ("ControlLeft", 1.25),
+            ("MetaLeft", 1.25),
+            ("AltLeft", 1.25),
+            ("NonConvert", 1.0),
+            ("Space", 3.25),
+            ("Convert", 1.0),
+            ("KanaMode", 1.0),
+        ],
+        &[],
+        &[
+            ("AltRight", 1.25),
+            ("MetaRight", 1.25),
+            ("ContextMenu", 1.25),
+            ("ControlRight", 1.25),
+        ],
+    ),
+];
+
+const ORTHO: Layout = [
+    (
+        &[("Backquote", 1.0)],
+        DIGITS,
+        &[("Minus", 1.0), ("Equal", 1.0), ("Backspace", 1.0)],
+    ),
+    (
+        &[("Tab", 1.0)],
+        LETTERS_TOP,
+        &[
+            ("BracketLeft", 1.0),
+            ("BracketRight", 1.0),
+            ("Backslash", 1.0),
+        ],
+    ),
+    (
+        &[("CapsLock", 1.0)],
+        LETTERS_HOME,
+        &[("Quote", 1.0), ("Enter", 1.0)],
+    ),
+    (
+        &[("ShiftLeft", 1.0)],
+        LETTERS_BOTTOM,
+        &[("ShiftRight", 1.0)],
+    ),
+    (
+        &[
+            ("ControlLeft", 1.0),
+            ("MetaLeft", 1.0),
+            ("AltLeft", 1.0),
+            ("", 2.0),
+            ("Space", 2.0),
+        ],
+        &[],
+        &[
+            ("AltRight", 1.0),
+            ("MetaRight", 1.0),
+            ("ContextMenu", 1.0),
+            ("ControlRight", 1.0),
+        ],
+    ),
+];
+
+const COLSTAG: Layout = [
+    (&[], DIGITS, &[]),
+    (&[], LETTERS_TOP, &[]),
+    (&[], LETTERS_HOME, &[]),
+    (&[], LETTERS_BOTTOM, &[]),
+    (&[("", 4.0), ("Space", 1.0)], &[], &[]),
+];
+
+/// Space between the two halves of [`Board::Colstag`], in key units.
+const SPLIT_GAP: f64 = 2.0;
+
+/// How far each column of the letter block is shifted down on [`Board::Colstag`], from the left
+/// pinky to the right pinky.
+const COLUMN_STAGGER: [f64; 10] = [
+    0.375, 0.125, 0.0, 0.125, 0.25, 0.25, 0.125, 0.0, 0.125, 0.375,
+];
+
+/// Finds `code` in `row` starting at `x`, returning the left edge and width of the key.
+fn find(row: Row, mut x: f64, code: &str) -> Option<(f64, f64)> {
+    for (key, width) in row {
+        if *key == code {
+            return Some((x, *width));
+        }
+        x += width;
+    }
+    None
+}
+
+fn width(row: Row) -> f64 {
+    row.iter().map(|(_, width)| width).sum()
+}
+
+impl Board {
+    fn layout(self) -> &'static Layout {
+        match self {
+            Self::Ansi => &ANSI,
+            Self::Iso => &ISO,
+            Self::Jis => &JIS,
+            Self::Ortho => &ORTHO,
+            Self::Colstag => &COLSTAG,
+        }
+    }
+
+    /// The geometry of the key with the given `KeyboardEvent.code`, if the board has it.
+    pub fn key_by_code(self, code: &str) -> Option<KeyGeometry> {
+        // Korean boards replace the right Alt and Control keys with Lang1 and Lang2.
+        let code = match (self, code) {
+            (Self::Ansi | Self::Iso, "Lang1") => "AltRight",
+            (Self::Ansi | Self::Iso, "Lang2") => "ControlRight",
+            _ => code,
+        };
+
+        if matches!(self, Self::Ansi | Self::Iso | Self::Jis) {
+            if let Some((left, width)) = find(FUNCTION_ROW, 0.0, code) {
+                return Some(KeyGeometry {
+                    x: left + width / 2.0,
+                    y: FUNCTION_ROW_Y,
+                    width,
+                    row_stagger: 0.0,
+                    column_stagger: 0.0,
+                });
+            }
+        }
+
+        let digits_start = width(self.layout()[0].0);
+
+        for (row, (left, letters, right)) in self.layout().iter().enumerate() {
+            let letters_start = width(left);
+            let right_start = letters_start + width(letters);
+
+            let Some((x, width)) = find(left, 0.0, code)
+                .or_else(|| find(letters, letters_start, code))
+                .or_else(|| find(right, right_start, code))
+            else {
+                continue;
+            };
+
+            let column = letters.iter().position(|(key, _)| *key == code);
+            let column_stagger = match (self, column) {
+                (Self::Colstag, Some(c)) => COLUMN_STAGGER[c],
+                (Self::Colstag, None) => COLUMN_STAGGER[4],
+                _ => 0.0,
+            };
+            let row_stagger = match letters.is_empty() {
+                true => 0.0,
+                false => letters_start - digits_start,
+            };
+            let tall_enter = code == "Enter" && matches!(self, Self::Iso | Self::Jis);
+            let split = match (self, column) {
+                (Self::Colstag, Some(5..)) => SPLIT_GAP,
+                _ => 0.0,
+            };
+
+            return Some(KeyGeometry {
+                x: x + width / 2.0 + split,
+                y: row as f64 + 0.5 + column_stagger + if tall_enter { 0.5 } else { 0.0 },
+                width,
+                row_stagger,
+                column_stagger,
+            });
+        }
+
+        None
+    }
+
+    /// The geometry of the key at `pos`, if the board has it.
+    pub fn key(self, pos: Pos) -> Option<KeyGeometry> {
+        self.key_by_code(pos.code()?)
+    }
+}
+
+impl Pos {
+    /// Where this key is on `board`. See [`geometry`](crate::geometry) for the coordinate system.
+    pub fn geometry(self, board: Board) -> Option<KeyGeometry> {
+        board.key(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn function_row_is_half_a_key_above_the_number_row() {
+        let f1 = Board::Ansi.key_by_code("F1").unwrap();
+        let digit = Board::Ansi.key_by_code("Digit1").unwrap();
+        assert_eq!(digit.y - digit.width / 2.0 - (f1.y + f1.width / 2.0), 0.5);
+    }
+
+    #[test]
+    fn colstag_halves_are_split() {
+        let t = Board::Colstag.key_by_code("KeyT").unwrap();
+        let y = Board::Colstag.key_by_code("KeyY").unwrap();
+        assert_eq!(y.x - t.x, 1.0 + SPLIT_GAP);
+
+        let g = Board::Ortho.key_by_code("KeyG").unwrap();
+        let h = Board::Ortho.key_by_code("KeyH").unwrap();
+        assert_eq!(h.x - g.x, 1.0);
+    }
+}
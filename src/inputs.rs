@@ -0,0 +1,252 @@
+//! Expanding directories and glob patterns into the list of files to load.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Extensions of the files that are picked up from directories when no include patterns are given.
+pub const DATASET_EXTENSIONS: &[&str] = &["json", "ttd", "csv", "tsv"];
+
+/// Which files to pick up when walking a directory. Patterns support `*` and `?` within a path
+/// component and `**` across components. Patterns without a `/` are matched against the file name,
+/// others against the path relative to the directory being walked.
+#[derive(Clone, Debug, Default)]
+pub struct InputFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl InputFilter {
+    fn includes(&self, rel: &str) -> bool {
+        let included = match self.include.is_empty() {
+            true => Path::new(rel)
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| DATASET_EXTENSIONS.contains(&e)),
+            false => self.include.iter().any(|p| pattern_matches(p, rel)),
+        };
+
+        included && !self.excludes(rel)
+    }
+
+    /// Like [`includes`](Self::includes) for files matched by a glob, which already picked them, so
+    /// they're only narrowed down by explicit include patterns.
+    fn includes_matched(&self, rel: &str) -> bool {
+        let included =
+            self.include.is_empty() || self.include.iter().any(|p| pattern_matches(p, rel));
+        included && !self.excludes(rel)
+    }
+
+    fn excludes(&self, rel: &str) -> bool {
+        self.exclude.iter().any(|p| pattern_matches(p, rel))
+    }
+
+    /// Expands every directory in `paths` into the files in it, recursively, and every path
+    /// containing `*` or `?` that doesn't exist into the files it matches. Both are filtered by the
+    /// include and exclude patterns, with paths relative to the directory or to the part of the
+    /// glob before its first wildcard. Files found this way are sorted; plain file paths are kept
+    /// as is and in order. Symlinks to directories aren't followed.
+    ///
+    /// A directory or glob that no file is picked up from is an error, rather than a dataset with
+    /// nothing in it.
+    pub fn expand<P: AsRef<Path>>(&self, paths: &[P]) -> io::Result<Vec<PathBuf>> {
+        let mut res = Vec::new();
+
+        for path in paths {
+            let path = path.as_ref();
+
+            if path.is_dir() {
+                let mut found = Vec::new();
+                walk(
+                    path,
+                    path,
+                    &mut |rel| self.excludes(rel),
+                    &mut |file, rel| {
+                        if self.includes(rel) {
+                            found.push(file)
+                        }
+                    },
+                )?;
+                found.sort();
+                if found.is_empty() {
+                    return Err(no_matches(path));
+                }
+                res.append(&mut found);
+            } else if !path.exists() && is_glob(&path.to_string_lossy()) {
+                let mut found = self.expand_glob(path)?;
+                if found.is_empty() {
+                    return Err(no_matches(path));
+                }
+                res.append(&mut found);
+            } else {
+                res.push(path.to_path_buf());
+            }
+        }
+
+        Ok(res)
+    }
+
+    /// Expands a glob like `data/**/*.json` by walking the longest directory prefix without
+    /// wildcards.
+    fn expand_glob(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut base = PathBuf::new();
+        let mut rest = Vec::new();
+
+        for component in path.components() {
+            let s = component.as_os_str().to_string_lossy();
+            match rest.is_empty() && !is_glob(&s) {
+                true => base.push(component),
+                false => rest.push(s.into_owned()),
+            }
+        }
+
+        let pattern = rest.join("/");
+        let root = match base.as_os_str().is_empty() {
+            true => PathBuf::from("."),
+            false => base,
+        };
+
+        let mut found = Vec::new();
+        walk(
+            &root,
+            &root,
+            &mut |rel| self.excludes(rel),
+            &mut |file, rel| {
+                if glob_matches(pattern.as_bytes(), rel.as_bytes()) && self.includes_matched(rel) {
+                    found.push(file)
+                }
+            },
+        )?;
+        found.sort();
+
+        Ok(found)
+    }
+}
+
+fn no_matches(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no input files match {}", path.display()),
+    )
+}
+
+fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?'])
+}
+
+/// Calls `f` with every file below `dir` and its path relative to `root`, using `/` as separator.
+/// Directories for which `skip` returns true aren't entered, and neither are symlinks to
+/// directories, which could loop forever.
+fn walk(
+    root: &Path,
+    dir: &Path,
+    skip: &mut dyn FnMut(&str) -> bool,
+    f: &mut dyn FnMut(PathBuf, &str),
+) -> io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let rel = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if !skip(&rel) {
+                walk(root, &path, skip, f)?;
+            }
+        } else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+            f(path, &rel);
+        }
+    }
+
+    Ok(())
+}
+
+fn pattern_matches(pattern: &str, rel: &str) -> bool {
+    match pattern.contains('/') {
+        true => glob_matches(pattern.as_bytes(), rel.as_bytes()),
+        false => {
+            let name = rel.rsplit('/').next().unwrap_or(rel);
+            glob_matches(pattern.as_bytes(), name.as_bytes())
+        }
+    }
+}
+
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', b'/', rest @ ..] => {
+            glob_matches(rest, text)
+                || (0..text.len())
+                    .filter(|&i| text[i] == b'/')
+                    .any(|i| glob_matches(rest, &text[i + 1..]))
+        }
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob_matches(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob_matches(rest, &text[i..])),
+        [b'?', rest @ ..] => {
+            matches!(text, [c, text @ ..] if *c != b'/' && glob_matches(rest, text))
+        }
+        [p, rest @ ..] => matches!(text, [c, text @ ..] if c == p && glob_matches(rest, text)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_path;
+
+    #[test]
+    fn globs_are_filtered_and_symlinks_to_directories_skipped() {
+        let dir = temp_path("inputs");
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        for file in ["a/one.json", "a/quotes.json", "two.json"] {
+            std::fs::write(dir.join(file), "{}").unwrap();
+        }
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("a/loop")).unwrap();
+
+        let filter = InputFilter {
+            exclude: vec!["*quotes*".to_string()],
+            ..Default::default()
+        };
+        let glob = dir.join("**/*.json");
+        let found = filter.expand(&[&dir, &glob]).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let expected = ["a/one.json", "two.json"].map(|file| dir.join(file));
+        assert_eq!(found, [expected.as_slice(), expected.as_slice()].concat());
+    }
+
+    #[test]
+    fn inputs_without_matches_are_errors() {
+        let dir = temp_path("empty-inputs");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let filter = InputFilter::default();
+        let empty_dir = filter.expand(&[&dir]);
+        let empty_glob = filter.expand(&[dir.join("*.json")]);
+        let included = InputFilter {
+            include: vec!["*.jsonl".to_string()],
+            ..Default::default()
+        }
+        .expand(&[&dir]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        for res in [empty_dir, empty_glob, included] {
+            let err = res.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::NotFound);
+            assert!(err.to_string().starts_with("no input files match "));
+        }
+    }
+}
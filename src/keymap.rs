@@ -0,0 +1,487 @@
+//! Which physical key and finger every `KeyboardEvent.code` corresponds to. Split and column
+//! staggered keyboards send the same codes as a row staggered board from keys in different places,
+//! so the analysis needs to know where each code actually is.
+//!
+//! Key maps are JSON files that either list every key or start from a preset and change some:
+//!
+//! ```json
+//! {
+//!     "name": "my corne",
+//!     "base": "colstag",
+//!     "keys": {
+//!         "KeyB": { "row": 2, "col": 4, "finger": "LI" },
+//!         "Quote": { "row": 1, "col": 9 },
+//!         "Semicolon": null
+//!     }
+//! }
+//! ```
+//!
+//! `row` and `col` are the position of the key. Rows 0 to 2 and columns 0 to 9 are the letter
+//! block, row -1 is the number row, columns -1 and 10 are the outer pinky columns and row 3 holds
+//! the thumb keys, see [`projection`](crate::projection) for which of them are analysed. `finger`
+//! defaults to the usual finger for the column, or the thumb of that half on row 3, and `null`
+//! removes a key from the map.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{LoadError, LoadErrorKind},
+    finger,
+    mapping::{code_index, str_eq, Pos},
+    metadata::DatasetMetadata,
+    trigram_patterns::Finger,
+};
+
+/// Names of the shipped key maps. `ansi`, `iso` and `jis` are row staggered boards, `ortho` a
+/// Planck style 4x12 grid and `colstag` a Corne style 3x6 split with three thumb keys per side.
+pub const PRESETS: &[&str] = &["ansi", "iso", "jis", "ortho", "colstag"];
+
+/// The letter block, which is the same on every preset. Rows and columns are cells of the grid
+/// described in the [module docs](self).
+pub const PRESET_KEYS: &[(&str, isize, isize)] = &[
+    ("KeyQ", 0, 0),
+    ("KeyW", 0, 1),
+    ("KeyE", 0, 2),
+    ("KeyR", 0, 3),
+    ("KeyT", 0, 4),
+    ("KeyY", 0, 5),
+    ("KeyU", 0, 6),
+    ("KeyI", 0, 7),
+    ("KeyO", 0, 8),
+    ("KeyP", 0, 9),
+    ("KeyA", 1, 0),
+    ("KeyS", 1, 1),
+    ("KeyD", 1, 2),
+    ("KeyF", 1, 3),
+    ("KeyG", 1, 4),
+    ("KeyH", 1, 5),
+    ("KeyJ", 1, 6),
+    ("KeyK", 1, 7),
+    ("KeyL", 1, 8),
+    ("Semicolon", 1, 9),
+    ("KeyZ", 2, 0),
+    ("KeyX", 2, 1),
+    ("KeyC", 2, 2),
+    ("KeyV", 2, 3),
+    ("KeyB", 2, 4),
+    ("KeyN", 2, 5),
+    ("KeyM", 2, 6),
+    ("Comma", 2, 7),
+    ("Period", 2, 8),
+    ("Slash", 2, 9),
+];
+
+/// Keys around the letter block of a row staggered board. `Space` is pressed by the right thumb.
+const ROW_STAGGER_KEYS: &[(&str, isize, isize)] = &[
+    ("Backquote", -1, -1),
+    ("Digit1", -1, 0),
+    ("Digit2", -1, 1),
+    ("Digit3", -1, 2),
+    ("Digit4", -1, 3),
+    ("Digit5", -1, 4),
+    ("Digit6", -1, 5),
+    ("Digit7", -1, 6),
+    ("Digit8", -1, 7),
+    ("Digit9", -1, 8),
+    ("Digit0", -1, 9),
+    ("Minus", -1, 10),
+    ("Tab", 0, -1),
+    ("BracketLeft", 0, 10),
+    ("CapsLock", 1, -1),
+    ("Quote", 1, 10),
+    ("Space", 3, 5),
+];
+
+/// The key between the left Shift and `KeyZ` of ISO boards.
+const ISO_KEYS: &[(&str, isize, isize)] = &[("IntlBackslash", 2, -1)];
+
+/// The key between `Slash` and the right Shift of JIS boards.
+const JIS_KEYS: &[(&str, isize, isize)] = &[("IntlRo", 2, 10)];
+
+/// The outer columns and the 2u space bar of a Planck. Numbers are on a layer, so they have no
+/// place of their own.
+const ORTHO_KEYS: &[(&str, isize, isize)] = &[
+    ("Tab", 0, -1),
+    ("Backspace", 0, 10),
+    ("Escape", 1, -1),
+    ("Quote", 1, 10),
+    ("Enter", 2, 10),
+    ("Space", 3, 5),
+];
+
+/// The outer columns and thumb keys of a Corne with its default keymap, which has `Space` on the
+/// inner left and `Enter` on the inner right thumb key. Modifiers are left out, see
+/// [`modifiers`](crate::modifiers).
+const COLSTAG_KEYS: &[(&str, isize, isize)] = &[
+    ("Tab", 0, -1),
+    ("Backspace", 0, 10),
+    ("Quote", 1, 10),
+    ("Escape", 2, 10),
+    ("Space", 3, 4),
+    ("Enter", 3, 5),
+];
+
+/// The keys of every preset besides the letter block.
+const fn board_keys(name: &str) -> &'static [&'static [(&'static str, isize, isize)]] {
+    match name.as_bytes() {
+        b"ansi" => &[ROW_STAGGER_KEYS],
+        b"iso" => &[ROW_STAGGER_KEYS, ISO_KEYS],
+        b"jis" => &[ROW_STAGGER_KEYS, JIS_KEYS],
+        b"ortho" => &[ORTHO_KEYS],
+        b"colstag" => &[COLSTAG_KEYS],
+        _ => &[],
+    }
+}
+
+/// The letter block by 3x10 index. [`PRESET_KEYS`] is checked against it at compile time, so the
+/// mapping can't silently change again.
+#[rustfmt::skip]
+const GOLDEN_3X10: [&str; 30] = [
+    "KeyQ", "KeyW", "KeyE", "KeyR", "KeyT", "KeyY", "KeyU", "KeyI", "KeyO", "KeyP",
+    "KeyA", "KeyS", "KeyD", "KeyF", "KeyG", "KeyH", "KeyJ", "KeyK", "KeyL", "Semicolon",
+    "KeyZ", "KeyX", "KeyC", "KeyV", "KeyB", "KeyN", "KeyM", "Comma", "Period", "Slash",
+];
+
+const _: () = {
+    let mut i = 0;
+    while i < PRESET_KEYS.len() {
+        let (code, row, col) = PRESET_KEYS[i];
+        assert!(code_index(code).is_some(), "unknown code in PRESET_KEYS");
+        assert!(
+            str_eq(GOLDEN_3X10[(row * 10 + col) as usize], code),
+            "PRESET_KEYS disagrees with GOLDEN_3X10"
+        );
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < PRESETS.len() {
+        let tables = board_keys(PRESETS[i]);
+        assert!(!tables.is_empty(), "preset without keys");
+        let mut j = 0;
+        while j < tables.len() {
+            let mut k = 0;
+            while k < tables[j].len() {
+                let (code, row, col) = tables[j][k];
+                assert!(code_index(code).is_some(), "unknown code in a preset");
+                let in_block = row >= 0 && row < 3 && col >= 0 && col < 10;
+                assert!(!in_block, "preset moves a key into the letter block");
+                k += 1;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+};
+
+/// Where a key sits on the grid described in the [module docs](self), and the finger that
+/// presses it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MappedKey {
+    pub row: isize,
+    pub col: isize,
+    pub finger: Finger,
+}
+
+impl MappedKey {
+    /// A key pressed by the usual finger for its column, or by a thumb on the thumb row.
+    pub fn new(row: isize, col: isize) -> Self {
+        let finger = match (row, col) {
+            (3, ..=4) => Finger::LT,
+            (3, _) => Finger::RT,
+            (_, ..=0) => Finger::LP,
+            (_, 9..) => Finger::RP,
+            (_, col) => Finger::from_usize(finger(col as usize)),
+        };
+
+        Self { row, col, finger }
+    }
+
+    pub fn cell(&self) -> (isize, isize) {
+        (self.row, self.col)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyMap {
+    pub name: String,
+    keys: HashMap<Pos, MappedKey>,
+    fingers: HashMap<(isize, isize), Finger>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyMapFile {
+    name: Option<String>,
+    base: Option<String>,
+    #[serde(default)]
+    keys: BTreeMap<String, Option<FileKey>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileKey {
+    row: isize,
+    col: isize,
+    finger: Option<Finger>,
+}
+
+fn invalid(message: impl Into<String>) -> LoadError {
+    LoadError::new(None, LoadErrorKind::KeyMap(message.into()))
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::preset("ansi").unwrap()
+    }
+}
+
+impl KeyMap {
+    /// An empty map named `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            keys: HashMap::new(),
+            fingers: HashMap::new(),
+        }
+    }
+
+    /// One of the [`PRESETS`].
+    pub fn preset(name: &str) -> Option<Self> {
+        let mut map = Self::new(name);
+
+        if !PRESETS.contains(&name) {
+            return None;
+        }
+
+        let keys = board_keys(name).iter().flat_map(|keys| keys.iter());
+        for (code, row, col) in PRESET_KEYS.iter().chain(keys) {
+            map.insert(code.parse().unwrap(), MappedKey::new(*row, *col));
+        }
+
+        Some(map)
+    }
+
+    /// The preset named by the dataset's `keyboard`, or the default map if there isn't one, with
+    /// the dataset's `mod` applied.
+    pub fn for_metadata(metadata: &DatasetMetadata) -> Self {
+        metadata
+            .keyboard
+            .as_deref()
+            .and_then(|keyboard| Self::preset(&keyboard.to_ascii_lowercase()))
+            .unwrap_or_default()
+            .with_mod(metadata.keyboard_mod.unwrap_or_default(), metadata.board())
+    }
+
+    /// Loads the preset called `name_or_path`, or a key map file if there is no such preset.
+    pub fn load<P: AsRef<Path>>(name_or_path: P) -> Result<Self, LoadError> {
+        let path = name_or_path.as_ref();
+
+        if let Some(map) = path.to_str().and_then(Self::preset) {
+            return Ok(map);
+        }
+        if !path.exists() && path.extension().is_none() {
+            return Err(invalid(format!(
+                "no preset or file called '{}', presets are {}",
+                path.display(),
+                PRESETS.join(", ")
+            )));
+        }
+
+        File::open(path)
+            .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))
+            .and_then(|f| Self::read(BufReader::new(f)))
+            .map_err(|mut e| {
+                e.path = Some(path.to_path_buf());
+                e
+            })
+    }
+
+    /// Reads a key map in the format described in the [module docs](self).
+    pub fn read<R: Read>(reader: R) -> Result<Self, LoadError> {
+        let file: KeyMapFile =
+            serde_json::from_reader(reader).map_err(|e| LoadError::from_json(None, e, None))?;
+
+        let mut map = match file.base.as_deref() {
+            Some(base) => {
+                Self::preset(base).ok_or_else(|| invalid(format!("unknown base map '{base}'")))?
+            }
+            None => Self::new(""),
+        };
+        map.name = file.name.or(file.base).unwrap_or_default();
+
+        for (code, key) in file.keys {
+            let pos = code
+                .parse::<Pos>()
+                .map_err(|_| invalid(format!("unknown key '{code}'")))?;
+
+            let Some(FileKey { row, col, finger }) = key else {
+                map.remove(pos);
+                continue;
+            };
+            if !(-1..=3).contains(&row) || !(-1..=10).contains(&col) {
+                return Err(invalid(format!(
+                    "{code} is at row {row}, col {col}, outside the grid"
+                )));
+            }
+
+            let mut key = MappedKey::new(row, col);
+            if let Some(finger) = finger {
+                key.finger = finger;
+            }
+            let thumb = matches!(key.finger, Finger::LT | Finger::RT);
+            if thumb != (row == 3) {
+                return Err(invalid(format!(
+                    "{code} is on row {row}, which is {}pressed by the thumbs",
+                    if thumb { "not " } else { "" }
+                )));
+            }
+            map.insert(pos, key);
+        }
+
+        for (pos, key) in map.keys.iter() {
+            if map.fingers.get(&key.cell()) != Some(&key.finger) {
+                return Err(invalid(format!(
+                    "{pos} is at the same matrix position as another key, but pressed by a different finger"
+                )));
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn insert(&mut self, pos: Pos, key: MappedKey) {
+        self.keys.insert(pos, key);
+        self.fingers.insert(key.cell(), key.finger);
+    }
+
+    pub fn remove(&mut self, pos: Pos) -> Option<MappedKey> {
+        let key = self.keys.remove(&pos)?;
+        if !self.keys.values().any(|k| k.cell() == key.cell()) {
+            self.fingers.remove(&key.cell());
+        }
+        Some(key)
+    }
+
+    /// Every mapped key, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = (Pos, MappedKey)> + '_ {
+        self.keys.iter().map(|(pos, key)| (*pos, *key))
+    }
+
+    pub fn get(&self, pos: Pos) -> Option<MappedKey> {
+        self.keys.get(&pos).copied()
+    }
+
+    /// The finger that presses the key in a cell, or the usual finger for the cell if no key is
+    /// mapped to it.
+    pub fn finger(&self, (row, col): (isize, isize)) -> Finger {
+        match self.fingers.get(&(row, col)) {
+            Some(finger) => *finger,
+            None => MappedKey::new(row, col).finger,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mapping::CODES, modifiers::ModifierPolicy, projection::Projection, TrigramData};
+
+    fn cell(map: &KeyMap, code: &str) -> Option<(isize, isize)> {
+        map.get(code.parse().unwrap()).map(|key| key.cell())
+    }
+
+    #[test]
+    fn presets_describe_their_boards() {
+        let ansi = KeyMap::preset("ansi").unwrap();
+        let iso = KeyMap::preset("iso").unwrap();
+        let jis = KeyMap::preset("jis").unwrap();
+        assert_eq!(cell(&ansi, "IntlBackslash"), None);
+        assert_eq!(cell(&iso, "IntlBackslash"), Some((2, -1)));
+        assert_eq!(cell(&jis, "IntlRo"), Some((2, 10)));
+        assert_eq!(cell(&ansi, "Space"), Some((3, 5)));
+
+        let ortho = KeyMap::preset("ortho").unwrap();
+        assert_eq!(cell(&ortho, "Digit1"), None);
+        assert_eq!(cell(&ortho, "Escape"), Some((1, -1)));
+        assert_eq!(cell(&ortho, "Enter"), Some((2, 10)));
+
+        let colstag = KeyMap::preset("colstag").unwrap();
+        assert_eq!(cell(&colstag, "Digit1"), None);
+        assert_eq!(cell(&colstag, "Escape"), Some((2, 10)));
+        assert_eq!(
+            colstag.get("Space".parse().unwrap()).unwrap().finger,
+            Finger::LT
+        );
+        assert_eq!(
+            colstag.get("Enter".parse().unwrap()).unwrap().finger,
+            Finger::RT
+        );
+
+        for name in PRESETS {
+            let map = KeyMap::preset(name).unwrap();
+            for (code, row, col) in PRESET_KEYS {
+                assert_eq!(cell(&map, code), Some((*row, *col)), "{name} {code}");
+            }
+        }
+    }
+
+    /// Loads a trigram of every code repeated three times and checks where each one ends up when
+    /// projected onto the 3x10 matrix with every preset. The expected indexes are written out here
+    /// rather than taken from [`GOLDEN_3X10`] or [`PRESET_KEYS`], so the test can't agree with a
+    /// mistake in them.
+    #[test]
+    fn every_key_projects_to_its_3x10_index() {
+        #[rustfmt::skip]
+        let rows = [
+            ["KeyQ", "KeyW", "KeyE", "KeyR", "KeyT", "KeyY", "KeyU", "KeyI", "KeyO", "KeyP"],
+            ["KeyA", "KeyS", "KeyD", "KeyF", "KeyG", "KeyH", "KeyJ", "KeyK", "KeyL", "Semicolon"],
+            ["KeyZ", "KeyX", "KeyC", "KeyV", "KeyB", "KeyN", "KeyM", "Comma", "Period", "Slash"],
+        ];
+        let expected = |pos: Pos| {
+            let code = pos.code().unwrap();
+            (0..30).find(|i| rows[i / 10][i % 10] == code)
+        };
+
+        let data = TrigramData::load("tests/fixtures/every_key.json").unwrap();
+        assert_eq!(data.trigrams.len(), CODES.len());
+
+        for name in PRESETS {
+            let keymap = KeyMap::preset(name).unwrap();
+            let projection = Projection::default();
+            for (_, pos) in CODES {
+                let index = projection.index(&keymap, *pos);
+                assert_eq!(index, expected(*pos), "{name} {pos}");
+            }
+
+            let mut data = data.clone();
+            data.metadata.keyboard = Some(name.to_string());
+            let (projected, _) = data
+                .clone()
+                .project(projection, keymap, ModifierPolicy::Drop)
+                .unwrap();
+
+            for matrix in [data.clone().matrix_3x10(), projected] {
+                assert_eq!(matrix.data.len(), 30, "{name}");
+                for (_, pos) in CODES {
+                    let Some(index) = expected(*pos) else {
+                        continue;
+                    };
+                    let samples = matrix.data.get(&[index; 3]).unwrap();
+                    assert_eq!(
+                        samples.values(),
+                        data.trigrams[&[*pos; 3]].values(),
+                        "{name} {pos}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,751 @@
+pub mod binary;
+pub mod dense;
+pub mod diff;
+pub mod error;
+pub mod events;
+pub mod firmware;
+pub mod geometry;
+pub mod inputs;
+pub mod keymap;
+pub mod load;
+pub mod mapping;
+pub mod metadata;
+pub mod modifiers;
+pub mod mods;
+pub mod outliers;
+pub mod projection;
+pub mod redact;
+pub mod sample;
+mod save;
+pub mod table;
+#[cfg(test)]
+mod testing;
+pub mod trigram_patterns;
+pub mod validate;
+mod with_dof;
+
+use dense::DenseTrigrams;
+use error::{LoadError, LoadErrorKind};
+use keymap::KeyMap;
+use load::{DatasetSeed, FileSummary, LoadMode, LoadOptions, LoadReport, LoadState};
+use mapping::*;
+use metadata::{DatasetMetadata, MetadataConflict};
+use modifiers::{is_modifier, CantFold, ModifierPolicy};
+use projection::{Projection, ProjectionAudit};
+use sample::{Micros, Samples, Source, SourceId};
+use table::TableFormat;
+use trigram_patterns::{Finger, TrigramPattern};
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use serde::{de::DeserializeSeed, Deserialize, Deserializer};
+
+/// Parses a trigram key like `"KeyA,KeyB,KeyC"`.
+pub fn parse_trigram(trigram: &str) -> Result<[Pos; 3], LoadErrorKind> {
+    let keys = trigram.split(',').collect::<Vec<_>>();
+
+    if keys.iter().any(|k| k.trim().is_empty()) {
+        return Err(LoadErrorKind::MalformedTrigram {
+            trigram: trigram.to_string(),
+        });
+    }
+
+    let [k1, k2, k3]: [&str; 3] =
+        keys.try_into()
+            .map_err(|keys: Vec<_>| LoadErrorKind::WrongArity {
+                trigram: trigram.to_string(),
+                found: keys.len(),
+            })?;
+
+    let parse = |key: &str| {
+        key.parse::<Pos>().map_err(|_| LoadErrorKind::UnknownKey {
+            trigram: trigram.to_string(),
+            key: key.to_string(),
+        })
+    };
+
+    Ok([parse(k1)?, parse(k2)?, parse(k3)?])
+}
+
+/// Trigram timings keyed by physical key positions. On disk this is either a bare
+/// `{"KeyA,KeyB,KeyC": [..]}` map as produced by the extension, or a versioned envelope:
+///
+/// ```json
+/// { "version": 1, "metadata": { "typist": "..", .. }, "trigrams": { "KeyA,KeyB,KeyC": [..] } }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TrigramData {
+    pub metadata: DatasetMetadata,
+    sources: Vec<Source>,
+    trigrams: HashMap<[Pos; 3], Samples>,
+    /// For data reconstructed with [`ModifierPolicy::Fold`], how many samples of every trigram had
+    /// a modifier folded into them. Those samples are part of `trigrams` as well. This isn't saved.
+    folded: Option<HashMap<[Pos; 3], usize>>,
+}
+
+impl<'de> Deserialize<'de> for TrigramData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut data = TrigramData::default();
+        let source = data.add_source(None);
+        DatasetSeed {
+            data: &mut data,
+            state: &mut LoadState::new(LoadMode::Strict, source),
+        }
+        .deserialize(deserializer)?;
+        Ok(data)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MatrixData {
+    pub metadata: DatasetMetadata,
+    pub sources: Vec<Source>,
+    /// The keys the data was projected onto. Matrix indexes are indexes into its cells.
+    pub projection: Projection,
+    /// The key map the data was projected with, which also decides the finger of every key.
+    pub keymap: KeyMap,
+    pub modifiers: ModifierPolicy,
+    data: DenseTrigrams,
+    /// Trigrams containing a modifier, kept only with [`ModifierPolicy::Separate`].
+    chorded: HashMap<[Pos; 3], Samples>,
+}
+
+impl TrigramData {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        Self::load_with_mode(path, LoadMode::Strict).map(|(data, _)| data)
+    }
+
+    /// Loads a dataset, reporting entries that were skipped in [`LoadMode::Lenient`].
+    pub fn load_with_mode<P: AsRef<Path>>(
+        path: P,
+        mode: LoadMode,
+    ) -> Result<(Self, LoadReport), LoadError> {
+        let mut data = Self::default();
+        let report = data.load_into(path, mode)?;
+        Ok((data, report))
+    }
+
+    /// Streams the dataset at `path` into `self`, parsing one entry at a time instead of reading
+    /// the whole file up front. `.csv` and `.tsv` files are read as [tables](table), anything else
+    /// as JSON or binary. On error `self` may contain part of the file.
+    pub fn load_into<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        mode: LoadMode,
+    ) -> Result<LoadReport, LoadError> {
+        let path = path.as_ref();
+
+        let mut report = File::open(path)
+            .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))
+            .and_then(|f| {
+                let name = Some(path.display().to_string());
+                match TableFormat::from_path(path) {
+                    Some(format) => self.read_table_into(BufReader::new(f), format, mode, name),
+                    None => self.read_named_into(BufReader::new(f), mode, name),
+                }
+            })
+            .map_err(|mut e| {
+                e.path = Some(path.to_path_buf());
+                e
+            })?;
+        report.set_path(path);
+
+        Ok(report)
+    }
+
+    /// Streams a dataset from `reader` into `self`. On error `self` may contain part of it.
+    pub fn read_into<R: Read>(
+        &mut self,
+        reader: R,
+        mode: LoadMode,
+    ) -> Result<LoadReport, LoadError> {
+        self.read_named_into(BufReader::new(reader), mode, None)
+    }
+
+    /// Reads either a JSON or a binary dataset, depending on how the data starts.
+    fn read_named_into<R: BufRead>(
+        &mut self,
+        mut reader: R,
+        mode: LoadMode,
+        name: Option<String>,
+    ) -> Result<LoadReport, LoadError> {
+        let header = reader
+            .fill_buf()
+            .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))?;
+
+        match binary::is_binary(header) {
+            true => self
+                .read_binary_into(reader)
+                .map_err(|kind| LoadError::new(None, kind)),
+            false => self.deserialize_into(
+                &mut serde_json::Deserializer::from_reader(reader),
+                mode,
+                name,
+            ),
+        }
+    }
+
+    pub fn from_json_str(json: &str) -> Result<Self, LoadError> {
+        Self::from_json_str_with_mode(json, LoadMode::Strict).map(|(data, _)| data)
+    }
+
+    pub fn from_json_str_with_mode(
+        json: &str,
+        mode: LoadMode,
+    ) -> Result<(Self, LoadReport), LoadError> {
+        let mut data = Self::default();
+        let report =
+            data.deserialize_into(&mut serde_json::Deserializer::from_str(json), mode, None)?;
+        Ok((data, report))
+    }
+
+    fn deserialize_into<'de, R: serde_json::de::Read<'de>>(
+        &mut self,
+        de: &mut serde_json::Deserializer<R>,
+        mode: LoadMode,
+        name: Option<String>,
+    ) -> Result<LoadReport, LoadError> {
+        let mut state = LoadState::new(mode, self.add_source(name));
+
+        DatasetSeed {
+            data: self,
+            state: &mut state,
+        }
+        .deserialize(&mut *de)
+        .and_then(|_| de.end())
+        .map_err(|e| LoadError::from_json(None, e, state.error.take()))?;
+
+        state.report.files.push(FileSummary {
+            path: None,
+            entries: state.entries,
+            samples: state.samples,
+        });
+
+        Ok(state.report)
+    }
+
+    pub fn load_multiple<P: AsRef<Path>>(paths: &[P]) -> Result<Self, LoadError> {
+        Self::load_multiple_with(paths, &LoadOptions::default()).map(|(data, _)| data)
+    }
+
+    /// Streams every file in `paths` into a single dataset. Directories and glob patterns are
+    /// expanded with [`InputFilter::expand`](inputs::InputFilter::expand), and the report lists
+    /// what every file contributed.
+    pub fn load_multiple_with<P: AsRef<Path>>(
+        paths: &[P],
+        options: &LoadOptions,
+    ) -> Result<(Self, LoadReport), LoadError> {
+        let paths = options
+            .filter
+            .expand(paths)
+            .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))?;
+
+        let mut data = Self::default();
+        let mut report = LoadReport::default();
+
+        for path in paths {
+            report.append(&mut data.load_into(path, options.mode)?);
+        }
+
+        Ok((data, report))
+    }
+
+    /// Appends the samples of `other` to this dataset, returning the warnings of merging their
+    /// metadata. Fails if the metadata of both datasets says they can't be compared, see
+    /// [`DatasetMetadata::merge`].
+    pub fn combine(mut self, other: Self) -> Result<(Self, Vec<String>), MetadataConflict> {
+        let (metadata, warnings) = self.metadata.merge(other.metadata)?;
+        self.metadata = metadata;
+
+        self.folded = match (self.folded.take(), other.folded) {
+            (Some(mut folded), Some(other)) => {
+                for (trigram, n) in other {
+                    *folded.entry(trigram).or_default() += n;
+                }
+                Some(folded)
+            }
+            (_, other) if self.trigrams.is_empty() => other,
+            _ => None,
+        };
+
+        let offset = self.sources.len() as SourceId;
+        self.sources.extend(other.sources);
+
+        for (trigram, mut samples) in other.trigrams {
+            samples.offset_sources(offset);
+            self.insert(trigram, samples);
+        }
+
+        Ok((self, warnings))
+    }
+
+    pub fn trigrams(&self) -> impl Iterator<Item = (&[Pos; 3], &Samples)> {
+        self.trigrams.iter()
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.trigrams.values().map(Samples::len).sum()
+    }
+
+    /// Every file or reader that contributed samples to this dataset, indexed by [`SourceId`].
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+
+    fn add_source(&mut self, name: Option<String>) -> SourceId {
+        self.sources.push(Source {
+            name,
+            metadata: DatasetMetadata::default(),
+        });
+        (self.sources.len() - 1) as SourceId
+    }
+
+    fn insert(&mut self, trigram: [Pos; 3], mut samples: Samples) {
+        self.trigrams
+            .entry(trigram)
+            .and_modify(|s| s.append(&mut samples))
+            .or_insert(samples);
+    }
+
+    /// Projects the data onto the 3x10 matrix with the key map for the dataset's `keyboard` and
+    /// `mod`, see [`KeyMap::for_metadata`].
+    pub fn matrix_3x10(self) -> MatrixData {
+        let keymap = KeyMap::for_metadata(&self.metadata);
+        let (data, _) = self.project_with(Projection::default(), keymap, ModifierPolicy::Drop);
+        data
+    }
+
+    /// Projects the data onto the keys of `projection`, dropping trigrams with keys that `keymap`
+    /// doesn't place in it. Trigrams of keys that share a matrix index are merged. Modifiers never
+    /// map to a key, trigrams containing one are handled according to `modifiers`.
+    ///
+    /// Fails with [`ModifierPolicy::Fold`] unless the data was reconstructed with it.
+    pub fn project(
+        self,
+        projection: Projection,
+        keymap: KeyMap,
+        modifiers: ModifierPolicy,
+    ) -> Result<(MatrixData, ProjectionAudit), CantFold> {
+        if modifiers == ModifierPolicy::Fold && self.folded.is_none() {
+            return Err(CantFold);
+        }
+        Ok(self.project_with(projection, keymap, modifiers))
+    }
+
+    fn project_with(
+        self,
+        projection: Projection,
+        keymap: KeyMap,
+        modifiers: ModifierPolicy,
+    ) -> (MatrixData, ProjectionAudit) {
+        let folded = self.folded.unwrap_or_default();
+        let index = |pos: Pos| projection.index(&keymap, pos);
+        let outside = |pos: Pos| !is_modifier(pos) && index(pos).is_none();
+        let mut audit = ProjectionAudit::new(&projection, &keymap);
+        let mut data = HashMap::<_, Samples>::new();
+        let mut chorded = HashMap::new();
+
+        for (poss, mut v) in self.trigrams {
+            if !poss.iter().any(|pos| is_modifier(*pos)) {
+                match poss.map(index) {
+                    [Some(a), Some(b), Some(c)] => {
+                        audit.keep(&poss, v.len());
+                        if let Some(n) = folded.get(&poss) {
+                            audit.folded.add(*n);
+                        }
+                        data.entry([a, b, c])
+                            .and_modify(|s| s.append(&mut v))
+                            .or_insert(v);
+                    }
+                    _ => audit.drop(&poss, v.len(), outside),
+                }
+                continue;
+            }
+
+            let mut keys = poss.iter().filter(|pos| !is_modifier(**pos)).peekable();
+            let in_matrix = keys.peek().is_some() && keys.all(|pos| index(*pos).is_some());
+
+            match (modifiers, in_matrix) {
+                (ModifierPolicy::Separate, true) => {
+                    audit.chorded.add(v.len());
+                    chorded.insert(poss, v);
+                }
+                (ModifierPolicy::Separate, false) => audit.drop(&poss, v.len(), outside),
+                _ => audit.drop(&poss, v.len(), |pos| is_modifier(pos) || outside(pos)),
+            }
+        }
+
+        let data = MatrixData {
+            metadata: self.metadata,
+            sources: self.sources,
+            data: DenseTrigrams::new(projection.len(), data),
+            projection,
+            keymap,
+            modifiers,
+            chorded,
+        };
+
+        (data, audit)
+    }
+}
+
+/// Mean and standard deviation of a set of samples, in ms.
+#[derive(Default, Debug, Clone)]
+pub struct Avg {
+    mean: f64,
+    sd: f64,
+    pop: usize,
+}
+
+impl Avg {
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn sd(&self) -> f64 {
+        self.sd
+    }
+
+    pub fn pop(&self) -> usize {
+        self.pop
+    }
+
+    pub fn new(data: Vec<Micros>) -> Self {
+        if data.is_empty() {
+            return Self {
+                mean: 0.0,
+                sd: 0.0,
+                pop: 0,
+            };
+        }
+
+        let mean = data.iter().map(|v| v.as_ms()).sum::<f64>() / data.len() as f64;
+        let sd = match data.len() {
+            1 => 0.0,
+            len => {
+                let sd_mean_corr_sum = data.iter().map(|v| (v.as_ms() - mean).powi(2)).sum::<f64>();
+                (sd_mean_corr_sum / ((len - 1) as f64)).sqrt()
+            }
+        };
+
+        Self {
+            mean,
+            sd,
+            pop: data.len(),
+        }
+    }
+}
+
+impl std::fmt::Display for Avg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "mean: {:.1}  sd: {:>4.1}  n: {:<4}  wpm: {:.0}",
+            self.mean,
+            self.sd,
+            self.pop,
+            60000.0 / self.mean * 2.0 / 5.0
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TrigramStatsInter {
+    overall: Vec<Micros>,
+    sfb: Vec<Micros>,
+    bad_sfb: Vec<Micros>,
+    sfs: Vec<Micros>,
+    sft: Vec<Micros>,
+    sfr: Vec<Micros>,
+    alternate: Vec<Micros>,
+    alternate_sfs: Vec<Micros>,
+    inroll: Vec<Micros>,
+    outroll: Vec<Micros>,
+    onehand: Vec<Micros>,
+    redirect: Vec<Micros>,
+    redirect_sfs: Vec<Micros>,
+    bad_redirect: Vec<Micros>,
+    bad_redirect_sfs: Vec<Micros>,
+    other: Vec<Micros>,
+    invalid: Vec<Micros>,
+    chorded: Option<Vec<Micros>>,
+}
+
+impl From<TrigramStatsInter> for TrigramStats {
+    fn from(stats: TrigramStatsInter) -> Self {
+        TrigramStats {
+            sfr: Avg::new(stats.sfr),
+            overall: Avg::new(stats.overall),
+            alternate: Avg::new(stats.alternate),
+            sfs: Avg::new(stats.sfs),
+            alternate_sfs: Avg::new(stats.alternate_sfs),
+            inroll: Avg::new(stats.inroll),
+            outroll: Avg::new(stats.outroll),
+            onehand: Avg::new(stats.onehand),
+            redirect: Avg::new(stats.redirect),
+            redirect_sfs: Avg::new(stats.redirect_sfs),
+            bad_redirect: Avg::new(stats.bad_redirect),
+            bad_redirect_sfs: Avg::new(stats.bad_redirect_sfs),
+            sfb: Avg::new(stats.sfb),
+            bad_sfb: Avg::new(stats.bad_sfb),
+            sft: Avg::new(stats.sft),
+            _other: Avg::new(stats.other),
+            _invalid: Avg::new(stats.invalid),
+            chorded: stats.chorded.map(Avg::new),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TrigramStats {
+    overall: Avg,
+    sfb: Avg,
+    sfr: Avg,
+    sfs: Avg,
+    bad_sfb: Avg,
+    sft: Avg,
+    alternate: Avg,
+    alternate_sfs: Avg,
+    inroll: Avg,
+    outroll: Avg,
+    onehand: Avg,
+    redirect: Avg,
+    redirect_sfs: Avg,
+    bad_redirect: Avg,
+    bad_redirect_sfs: Avg,
+    _other: Avg,
+    _invalid: Avg,
+    /// Only reported with [`ModifierPolicy::Separate`].
+    chorded: Option<Avg>,
+}
+
+impl TrigramStats {
+    /// Every reported category with its name, in the order they're displayed.
+    pub fn categories(&self) -> [(&'static str, &Avg); 15] {
+        [
+            ("Overall", &self.overall),
+            ("Sfb", &self.sfb),
+            ("BadSfb", &self.bad_sfb),
+            ("Sft", &self.sft),
+            ("Sfr", &self.sfr),
+            ("Sfs", &self.sfs),
+            ("Alternate", &self.alternate),
+            ("Alternate Sfs", &self.alternate_sfs),
+            ("Inroll", &self.inroll),
+            ("Outroll", &self.outroll),
+            ("Onehand", &self.onehand),
+            ("Redirect", &self.redirect),
+            ("RedirectSfs", &self.redirect_sfs),
+            ("BadRedirect", &self.bad_redirect),
+            ("BadRedirectSfs", &self.bad_redirect_sfs),
+        ]
+    }
+
+    /// Stats of trigrams typed while holding a modifier, if they're reported separately.
+    pub fn chorded(&self) -> Option<&Avg> {
+        self.chorded.as_ref()
+    }
+}
+
+impl std::fmt::Display for TrigramStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            concat!(
+                "Overall:        {}\n\n",
+                "Sfb:            {}\n",
+                "BadSfb:         {}\n",
+                "Sft:            {}\n",
+                "Sfr:            {}\n",
+                "Sfs:            {}\n\n",
+                "Alternate:      {}\n",
+                "Alternate Sfs:  {}\n\n",
+                "Inroll:         {}\n",
+                "Outroll:        {}\n",
+                "Onehand:        {}\n\n",
+                "Redirect:       {}\n",
+                "RedirectSfs:    {}\n",
+                "BadRedirect:    {}\n",
+                "BadRedirectSfs: {}\n\n",
+                // "Other:          {}\n",
+                // "Invalid:        {}\n",
+            ),
+            self.overall,
+            self.sfb,
+            self.bad_sfb,
+            self.sft,
+            self.sfr,
+            self.sfs,
+            self.alternate,
+            self.alternate_sfs,
+            self.inroll,
+            self.outroll,
+            self.onehand,
+            self.redirect,
+            self.redirect_sfs,
+            self.bad_redirect,
+            self.bad_redirect_sfs,
+            // self.other,
+            // self.invalid,
+        )?;
+
+        match &self.chorded {
+            Some(chorded) => write!(f, "Chorded:        {chorded}\n\n"),
+            None => Ok(()),
+        }
+    }
+}
+
+fn finger(index: usize) -> usize {
+    // match (index / 10, index % 10) {
+    //     (3, 0) => Finger::LT,
+    //     (_, 0) => Finger::LP,
+    //     (_, 1) => Finger::LR,
+    //     (_, 2) => Finger::LM,
+    //     (_, 3) | (_, 4) => Finger::LI,
+    //     (_, 5) | (_, 6)=> Finger::RI,
+    //     (_, 7) => Finger::RM,
+    //     (_, 8) => Finger::RR,
+    //     (_, 9) => Finger::RP,
+    //     _ => unreachable!()
+    // }
+    match index % 10 {
+        n @ 0..=3 => n,
+        n @ (4 | 5) => n - 1,
+        n @ 6..=9 => n - 2,
+        _ => unreachable!(),
+    }
+}
+
+fn fingers_are_sfs([a, b, c]: &[usize; 3]) -> bool {
+    a == c && a != b
+}
+
+pub(crate) fn indexes_are_sfr([a, b, c]: &[usize; 3]) -> bool {
+    a == b || b == c
+}
+
+impl MatrixData {
+    /// The fingers that press the keys at matrix indexes.
+    pub fn fingers(&self, indexes: &[usize; 3]) -> [Finger; 3] {
+        indexes.map(|index| self.projection.finger(&self.keymap, index))
+    }
+
+    /// Pattern of a trigram of matrix indexes, going by the fingers of the key map.
+    pub fn trigram_pattern(&self, indexes: &[usize; 3]) -> TrigramPattern {
+        TrigramPattern::from_fingers(self.fingers(indexes))
+    }
+
+    /// The samples of every trigram of matrix indexes.
+    pub fn trigrams(&self) -> &DenseTrigrams {
+        &self.data
+    }
+
+    pub fn stats(&self) -> TrigramStats {
+        self.stats_for(|_| true)
+    }
+
+    /// Stats over only the samples whose source matches `f`.
+    pub fn stats_for(&self, f: impl Fn(SourceId) -> bool) -> TrigramStats {
+        let mut inter = TrigramStatsInter::default();
+
+        for (indexes, samples) in self.data.iter() {
+            let vals = samples.filter_source(&f).collect::<Vec<_>>();
+            if vals.is_empty() {
+                continue;
+            }
+            let (indexes, vals) = (&indexes, &vals);
+
+            if indexes_are_sfr(indexes) {
+                inter.sfr.extend(vals);
+                inter.overall.extend(vals);
+                continue;
+            }
+
+            if fingers_are_sfs(&self.fingers(indexes).map(|f| f as usize)) {
+                inter.sfs.extend(vals)
+            }
+
+            inter.overall.extend(vals);
+
+            use trigram_patterns::TrigramPattern as T;
+
+            match self.trigram_pattern(indexes) {
+                T::Alternate => inter.alternate.extend(vals),
+                T::AlternateSfs => inter.alternate_sfs.extend(vals),
+                T::Inroll => inter.inroll.extend(vals),
+                T::Outroll => inter.outroll.extend(vals),
+                T::Onehand => inter.onehand.extend(vals),
+                T::Redirect => inter.redirect.extend(vals),
+                T::RedirectSfs => inter.redirect_sfs.extend(vals),
+                T::BadRedirect => inter.bad_redirect.extend(vals),
+                T::BadRedirectSfs => inter.bad_redirect_sfs.extend(vals),
+                T::Sfb => inter.sfb.extend(vals),
+                T::BadSfb => inter.bad_sfb.extend(vals),
+                T::Sft => inter.sft.extend(vals),
+                T::Other => inter.other.extend(vals),
+            }
+        }
+
+        if self.modifiers == ModifierPolicy::Separate {
+            let chorded = self
+                .chorded
+                .values()
+                .flat_map(|samples| samples.filter_source(&f));
+            inter.chorded = Some(chorded.collect());
+        }
+
+        inter.into()
+    }
+
+    /// Stats for every source separately, in the order the sources were loaded.
+    pub fn stats_by_source(&self) -> Vec<(&Source, TrigramStats)> {
+        self.sources
+            .iter()
+            .enumerate()
+            .map(|(id, source)| (source, self.stats_for(|s| s == id as SourceId)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_by_source_split_interleaved_samples() {
+        let mut data = TrigramData::default();
+        let a = data.add_source(Some("a".to_string()));
+        let b = data.add_source(Some("b".to_string()));
+
+        let trigram = parse_trigram("KeyA,KeyS,KeyD").unwrap();
+        let ms = |ms: &[u32]| ms.iter().map(|ms| Micros(ms * 1000)).collect();
+        data.insert(trigram, Samples::new(a, ms(&[100, 120])));
+        data.insert(trigram, Samples::new(b, ms(&[200])));
+        data.insert(trigram, Samples::new(a, ms(&[140])));
+        data.insert(trigram, Samples::new(b, ms(&[220, 240])));
+        assert_eq!(
+            data.trigrams[&trigram].runs(),
+            [(a, 2), (b, 1), (a, 1), (b, 2)]
+        );
+
+        let matrix = data.matrix_3x10();
+        let overall = |stats: &TrigramStats| {
+            let avg = stats.categories()[0].1;
+            (avg.mean(), avg.pop())
+        };
+        assert_eq!(overall(&matrix.stats()), (170.0, 6));
+
+        let by_source = matrix.stats_by_source();
+        assert_eq!(by_source.len(), 2);
+        assert_eq!(by_source[0].0.name.as_deref(), Some("a"));
+        assert_eq!(overall(&by_source[0].1), (120.0, 3));
+        assert_eq!(by_source[1].0.name.as_deref(), Some("b"));
+        assert_eq!(overall(&by_source[1].1), (220.0, 3));
+    }
+}
@@ -0,0 +1,378 @@
+use std::path::PathBuf;
+
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, Visitor};
+
+use crate::{
+    error::LoadErrorKind,
+    inputs::InputFilter,
+    metadata::{DatasetMetadata, SCHEMA_VERSION},
+    parse_trigram,
+    sample::{Micros, Samples, SourceId},
+    TrigramData,
+};
+
+/// What to do with trigram keys that can't be parsed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LoadMode {
+    /// Fail the whole load.
+    #[default]
+    Strict,
+    /// Drop the entry and record it in the [`LoadReport`].
+    Lenient,
+}
+
+/// A trigram entry that was dropped while loading in [`LoadMode::Lenient`]. Tables have a row per
+/// sample, so their rows are grouped by the kind of error instead, and `trigram` and `reason` are
+/// those of the first row of the group.
+#[derive(Debug)]
+pub struct RejectedTrigram {
+    pub path: Option<PathBuf>,
+    pub trigram: String,
+    pub samples: usize,
+    pub reason: LoadErrorKind,
+    /// Number of entries or rows this stands for.
+    pub entries: usize,
+    /// The first few lines of the rows, for tables.
+    pub lines: Vec<usize>,
+}
+
+impl RejectedTrigram {
+    /// How many lines of a group are kept.
+    pub const MAX_LINES: usize = 5;
+
+    /// Adds another row with the same kind of error at `line`.
+    pub(crate) fn add_row(&mut self, line: usize) {
+        self.entries += 1;
+        self.samples += 1;
+        if self.lines.len() < Self::MAX_LINES {
+            self.lines.push(line);
+        }
+    }
+}
+
+/// How much a single file contributed to a dataset.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileSummary {
+    pub path: Option<PathBuf>,
+    /// Number of trigram entries loaded from the file.
+    pub entries: usize,
+    pub samples: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    pub files: Vec<FileSummary>,
+    pub rejected: Vec<RejectedTrigram>,
+    /// Metadata fields that were cleared because the datasets disagreed on them, see
+    /// [`DatasetMetadata::merge`].
+    pub warnings: Vec<String>,
+}
+
+impl LoadReport {
+    pub fn has_rejected(&self) -> bool {
+        !self.rejected.is_empty()
+    }
+
+    pub fn has_warnings(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+
+    pub fn rejected_samples(&self) -> usize {
+        self.rejected.iter().map(|r| r.samples).sum()
+    }
+
+    /// Number of trigram entries or table rows that were skipped.
+    pub fn rejected_entries(&self) -> usize {
+        self.rejected.iter().map(|r| r.entries).sum()
+    }
+
+    pub(crate) fn set_path(&mut self, path: &std::path::Path) {
+        for file in self.files.iter_mut() {
+            file.path = Some(path.to_path_buf());
+        }
+        for rejected in self.rejected.iter_mut() {
+            rejected.path = Some(path.to_path_buf());
+        }
+    }
+
+    pub fn append(&mut self, other: &mut Self) {
+        self.files.append(&mut other.files);
+        self.rejected.append(&mut other.rejected);
+        self.warnings.append(&mut other.warnings);
+    }
+}
+
+impl std::fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for file in self.files.iter() {
+            if let Some(path) = &file.path {
+                write!(f, "{}: ", path.display())?;
+            }
+            writeln!(f, "{} trigrams, {} samples", file.entries, file.samples)?;
+        }
+        for r in self.rejected.iter() {
+            if let Some(path) = &r.path {
+                write!(f, "{}: ", path.display())?;
+            }
+            match r.lines.as_slice() {
+                [] => writeln!(f, "skipped {} samples: {}", r.samples, r.reason)?,
+                lines => {
+                    let lines = lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+                    let more = match r.entries > lines.len() {
+                        true => " and more",
+                        false => "",
+                    };
+                    writeln!(
+                        f,
+                        "skipped {} rows on lines {}{more}, like {}",
+                        r.entries,
+                        lines.join(", "),
+                        r.reason
+                    )?
+                }
+            }
+        }
+        if self.has_rejected() {
+            writeln!(
+                f,
+                "skipped {} trigrams with {} samples in total",
+                self.rejected_entries(),
+                self.rejected_samples()
+            )?;
+        }
+        for warning in self.warnings.iter() {
+            writeln!(f, "warning: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for [`TrigramData::load_multiple_with`].
+#[derive(Clone, Debug, Default)]
+pub struct LoadOptions {
+    pub mode: LoadMode,
+    /// Which files to load from directories passed as inputs.
+    pub filter: InputFilter,
+}
+
+/// Shared by the visitors while a single dataset is deserialized.
+pub(crate) struct LoadState {
+    pub mode: LoadMode,
+    /// The source every sample that's loaded is attributed to.
+    pub source: SourceId,
+    /// Serde errors can only carry a message, so the structured error of anything that's rejected
+    /// is stashed here for [`LoadError`](crate::error::LoadError) to pick up.
+    pub error: Option<LoadErrorKind>,
+    pub report: LoadReport,
+    pub entries: usize,
+    pub samples: usize,
+}
+
+impl LoadState {
+    pub fn new(mode: LoadMode, source: SourceId) -> Self {
+        Self {
+            mode,
+            source,
+            error: None,
+            report: LoadReport::default(),
+            entries: 0,
+            samples: 0,
+        }
+    }
+
+    fn stash<E: de::Error>(&mut self, kind: LoadErrorKind) -> E {
+        let err = E::custom(&kind);
+        self.error = Some(kind);
+        err
+    }
+
+    fn next_entry<'de, A: MapAccess<'de>>(
+        &mut self,
+        key: String,
+        map: &mut A,
+        data: &mut TrigramData,
+    ) -> Result<(), A::Error> {
+        match parse_trigram(&key) {
+            Ok(trigram) => {
+                let samples = Samples::new(self.source, map.next_value()?);
+                self.entries += 1;
+                self.samples += samples.len();
+                data.insert(trigram, samples);
+            }
+            Err(reason) if self.mode == LoadMode::Lenient => {
+                let samples = map.next_value::<Vec<Micros>>()?.len();
+                self.report.rejected.push(RejectedTrigram {
+                    path: None,
+                    trigram: key,
+                    samples,
+                    reason,
+                    entries: 1,
+                    lines: Vec::new(),
+                });
+            }
+            Err(reason) => return Err(self.stash(reason)),
+        }
+
+        Ok(())
+    }
+}
+
+/// Deserializes one dataset, merging its entries into `data` one at a time so that loading many
+/// files only ever holds the accumulated dataset plus the entry being parsed.
+///
+/// If loading fails, `data` may already contain some of the entries of the failed dataset.
+pub(crate) struct DatasetSeed<'a> {
+    pub data: &'a mut TrigramData,
+    pub state: &'a mut LoadState,
+}
+
+impl<'de> DeserializeSeed<'de> for DatasetSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DatasetSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a map of trigrams or a versioned dataset")
+    }
+
+    /// The version is checked as soon as it's read, so the trigrams of an envelope this crate can't
+    /// read are never parsed. Envelopes write it first.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut version = None;
+        let mut has_metadata = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" => match map.next_value::<u32>()? {
+                    v @ 1..=SCHEMA_VERSION => version = Some(v),
+                    v => return Err(self.state.stash(LoadErrorKind::UnsupportedVersion(v))),
+                },
+                "metadata" => {
+                    let metadata = map.next_value::<DatasetMetadata>()?;
+                    has_metadata = !metadata.is_empty();
+                    self.data.sources[self.state.source as usize].metadata = metadata.clone();
+
+                    let current = std::mem::take(&mut self.data.metadata);
+                    let (merged, mut warnings) = current
+                        .merge(metadata)
+                        .map_err(|c| self.state.stash(LoadErrorKind::Incompatible(c)))?;
+                    self.data.metadata = merged;
+                    self.state.report.warnings.append(&mut warnings);
+                }
+                "trigrams" => map.next_value_seed(TrigramsSeed {
+                    data: self.data,
+                    state: self.state,
+                })?,
+                _ => self.state.next_entry(key, &mut map, self.data)?,
+            }
+        }
+
+        match version {
+            None if has_metadata => Err(self.state.stash(LoadErrorKind::MissingVersion)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The `"trigrams"` map of an envelope, inserted straight into the dataset being built.
+struct TrigramsSeed<'a> {
+    data: &'a mut TrigramData,
+    state: &'a mut LoadState,
+}
+
+impl<'de> DeserializeSeed<'de> for TrigramsSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TrigramsSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a map of trigrams")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            self.state.next_entry(key, &mut map, self.data)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_error(json: &str) -> LoadErrorKind {
+        TrigramData::from_json_str(json).unwrap_err().kind
+    }
+
+    #[test]
+    fn envelope_versions_are_checked_before_the_trigrams() {
+        let envelope = r#"{"version": 1, "metadata": {"typist": "maya"},
+            "trigrams": {"KeyA,KeyB,KeyC": [90]}}"#;
+        let data = TrigramData::from_json_str(envelope).unwrap();
+        assert_eq!(data.metadata.typist.as_deref(), Some("maya"));
+        assert_eq!(data.sample_count(), 1);
+
+        for version in [0, 2] {
+            let json = format!(r#"{{"version": {version}, "trigrams": {{"KeyA,,KeyC": [90]}}}}"#);
+            assert!(matches!(
+                load_error(&json),
+                LoadErrorKind::UnsupportedVersion(v) if v == version
+            ));
+        }
+
+        let unversioned = r#"{"metadata": {"typist": "maya"}, "trigrams": {}}"#;
+        assert!(matches!(
+            load_error(unversioned),
+            LoadErrorKind::MissingVersion
+        ));
+
+        let bare = TrigramData::from_json_str(r#"{"KeyA,KeyB,KeyC": [90, 100]}"#).unwrap();
+        assert!(bare.metadata.is_empty());
+        assert_eq!(bare.sample_count(), 2);
+    }
+
+    #[test]
+    fn lenient_loads_report_what_they_skipped() {
+        let json = r#"{"KeyA,KeyB,KeyC": [90, 100], "KeyA,Nope,KeyC": [80, 85, 95],
+            "KeyA,,KeyC": [70], "KeyD,KeyE,KeyF": [110]}"#;
+
+        let strict = TrigramData::from_json_str(json).unwrap_err();
+        assert!(matches!(strict.kind, LoadErrorKind::UnknownKey { .. }));
+
+        let (data, report) = TrigramData::from_json_str_with_mode(json, LoadMode::Lenient).unwrap();
+        assert_eq!(data.sample_count(), 3);
+        assert_eq!(
+            report.files,
+            [FileSummary {
+                path: None,
+                entries: 2,
+                samples: 3,
+            }]
+        );
+        assert!(report.has_rejected());
+        assert_eq!(report.rejected.len(), 2);
+        assert_eq!(report.rejected_samples(), 4);
+        assert_eq!(report.rejected[0].trigram, "KeyA,Nope,KeyC");
+        assert!(matches!(
+            report.rejected[1].reason,
+            LoadErrorKind::MalformedTrigram { .. }
+        ));
+        assert!(report
+            .to_string()
+            .ends_with("skipped 2 trigrams with 4 samples in total\n"));
+    }
+}
@@ -1,364 +1,360 @@
-mod mapping;
-mod trigram_patterns;
-mod with_dof;
-
-use mapping::*;
-use trigram_patterns::TRIGRAM_COMBINATIONS;
-
-use std::{collections::HashMap, fs::File, io::Read, path::Path};
-
-use serde::{Deserialize, Serialize};
-use serde_json;
-use serde_with::{serde_as, serde_conv};
-
-serde_conv!(
-    TrigramAsPos,
-    [Pos; 3],
-    |trigram: &[Pos; 3]| format!("{},{},{}", trigram[0], trigram[1], trigram[2]),
-    |value: String| {
-        value
-            .split(",")
-            .map(|k| str::parse::<Pos>(k))
-            .collect::<Result<Vec<_>, String>>()?
-            .try_into()
-            .map_err(|_| "Couldn't turn trigram str into pos trigram".to_string())
-    }
-);
-
-#[serde_as]
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
-pub struct TrigramData(#[serde_as(as = "HashMap<TrigramAsPos, _>")] HashMap<[Pos; 3], Vec<u16>>);
-
-#[derive(Clone, Debug)]
-pub struct MatrixData(HashMap<[usize; 3], Vec<u16>>);
-
-impl TrigramData {
-    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
-        let mut f = File::open(path).map_err(|e| e.to_string())?;
-
-        let mut buf = String::with_capacity(f.metadata().unwrap().len() as usize);
-        f.read_to_string(&mut buf).map_err(|e| e.to_string())?;
-
-        serde_json::from_str(&buf).map_err(|e| e.to_string())
-    }
-
-    pub fn load_multiple<P: AsRef<Path>>(paths: &[P]) -> Result<Self, String> {
-        let datas = paths
-            .into_iter()
-            .map(|p| TrigramData::load(p).map_err(|e| e.to_string()))
-            .collect::<Result<Vec<_>, String>>()?;
-
-        let data = datas
-            .into_iter()
-            .reduce(|acc, d| acc.combine(d))
-            .unwrap_or_default();
+use trigram_timing_data::{
+    error::{LoadError, LoadErrorKind},
+    events::{EventLog, Reconstructor},
+    firmware::ImportOptions,
+    inputs::InputFilter,
+    keymap::KeyMap,
+    load::{FileSummary, LoadMode, LoadOptions, LoadReport},
+    metadata::DatasetMetadata,
+    modifiers::ModifierPolicy,
+    outliers::OutlierPolicy,
+    projection::Projection,
+    redact::RedactOptions,
+    sample::Micros,
+    table::TableFormat,
+    validate::ValidateOptions,
+    TrigramData,
+};
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("error: {msg}");
+    std::process::exit(1);
+}
 
-        Ok(data)
+/// Reconstructs trigram data from raw event logs instead of loading trigram files.
+fn load_events(
+    paths: &[String],
+    filter: &InputFilter,
+    reconstructor: Reconstructor,
+) -> Result<(TrigramData, LoadReport), LoadError> {
+    let paths = filter
+        .expand(paths)
+        .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))?;
+
+    let mut data = TrigramData::default();
+    let mut report = LoadReport::default();
+
+    for path in paths {
+        let trigrams = reconstructor.trigrams(&EventLog::load(&path)?);
+        report.files.push(FileSummary {
+            path: Some(path.clone()),
+            entries: trigrams.trigrams().count(),
+            samples: trigrams.sample_count(),
+        });
+        let (combined, mut warnings) = data
+            .combine(trigrams)
+            .map_err(|c| LoadError::new(Some(path), LoadErrorKind::Incompatible(c)))?;
+        data = combined;
+        report.warnings.append(&mut warnings);
     }
 
-    pub fn combine(self, other: Self) -> Self {
-        let mut res = Self::default();
+    Ok((data, report))
+}
 
-        for (trigram, mut freqs) in self.0 {
-            res.0
-                .entry(trigram)
-                .and_modify(|f| f.append(&mut freqs))
-                .or_insert(freqs);
-        }
+/// Concatenates raw event logs without reconstructing trigrams from them.
+fn load_event_log(paths: &[String], filter: &InputFilter) -> EventLog {
+    let paths = filter.expand(paths).unwrap_or_else(|e| fail(e));
+    let mut log = EventLog::default();
 
-        for (trigram, mut freqs) in other.0 {
-            res.0
-                .entry(trigram)
-                .and_modify(|f| f.append(&mut freqs))
-                .or_insert(freqs);
+    for path in paths {
+        match EventLog::load(&path) {
+            Ok(mut events) => log.events.append(&mut events.events),
+            Err(e) => fail(e),
         }
-
-        res
     }
 
-    pub fn matrix_3x10(self) -> MatrixData {
-        let new_data = self
-            .0
-            .into_iter()
-            .filter(|(poss, _)| {
-                poss.into_iter().all(|p| match p.row {
-                    1 | 2 => p.col > 0 && p.col <= 10,
-                    3 => p.col <= 12,
-                    // 4 => p.col == 0 || p.col == 1,
-                    _ => false,
-                })
-            })
-            .map(|(poss, v)| {
-                let tri = poss
-                    .into_iter()
-                    .map(|Pos { row, col }| {
-                        let Pos { row, col } = match row {
-                            1 | 2 => Pos {
-                                row: row - 1,
-                                col: col - 1,
-                            },
-                            3 => match col {
-                                0 | 1 => Pos { row: 0, col: 2 },
-                                n @ (2..=11) => Pos { row: n - 2, col: 2 },
-                                12 => Pos { row: 9, col: 2 },
-                                _ => unreachable!(),
-                            },
-                            _ => unreachable!(),
-                        };
-                        row * 10 + col
-                    })
-                    .collect::<Vec<_>>()
-                    .try_into()
-                    .unwrap();
-                (tri, v)
-            })
-            .collect::<HashMap<_, _>>();
-        MatrixData(new_data)
-    }
+    log
 }
 
-#[derive(Default, Debug, Clone)]
-pub struct Avg {
-    mean: u16,
-    sd: u16,
-    pop: usize,
+fn flag_value(args: &mut impl Iterator<Item = String>, flag: &str) -> String {
+    match args.next() {
+        Some(value) => value,
+        None => fail(format!("{flag} needs a value")),
+    }
 }
 
-impl Avg {
-    pub fn new(data: Vec<u16>) -> Self {
-        if data.len() == 0 {
-            return Self {
-                mean: 0,
-                sd: 0,
-                pop: 0,
-            };
-        }
-
-        let mean = data.iter().map(|v| *v as f64).sum::<f64>() / data.len() as f64;
-        let sd_mean_corr_sum = data.iter().map(|v| (*v as f64 - mean).powi(2)).sum::<f64>();
-        let sd = (sd_mean_corr_sum / ((data.len() - 1) as f64)).sqrt();
-
-        let mean = mean as u16;
-        let sd = sd as u16;
-
-        Self {
-            mean,
-            sd,
-            pop: data.len(),
+/// `keymap` with the dataset's mod applied, or the key map for the dataset if there is none.
+fn keymap_for(keymap: Option<KeyMap>, metadata: &DatasetMetadata) -> KeyMap {
+    let keyboard_mod = metadata.keyboard_mod.unwrap_or_default();
+    match keymap {
+        Some(keymap) => {
+            let board = keymap.name.parse().unwrap_or(metadata.board());
+            keymap.with_mod(keyboard_mod, board)
         }
+        None => KeyMap::for_metadata(metadata),
     }
 }
 
-impl std::fmt::Display for Avg {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "mean: {}  sd: {:>2}  n: {:<4}  wpm: {}",
-            self.mean,
-            self.sd,
-            self.pop,
-            60000 / self.mean * 2 / 5
-        )
+/// Prints the full load report if asked to or if anything was skipped, and its warnings otherwise.
+fn print_report(report: &LoadReport, verbose: bool) {
+    if verbose || report.has_rejected() {
+        eprintln!("{report}");
+        return;
+    }
+    for warning in report.warnings.iter() {
+        eprintln!("warning: {warning}");
     }
 }
 
-#[derive(Debug, Default, Clone)]
-pub struct TrigramStatsInter {
-    overall: Vec<u16>,
-    sfb: Vec<u16>,
-    bad_sfb: Vec<u16>,
-    sfs: Vec<u16>,
-    sft: Vec<u16>,
-    sfr: Vec<u16>,
-    alternate: Vec<u16>,
-    alternate_sfs: Vec<u16>,
-    inroll: Vec<u16>,
-    outroll: Vec<u16>,
-    onehand: Vec<u16>,
-    redirect: Vec<u16>,
-    redirect_sfs: Vec<u16>,
-    bad_redirect: Vec<u16>,
-    bad_redirect_sfs: Vec<u16>,
-    other: Vec<u16>,
-    invalid: Vec<u16>,
+/// Saves in the binary format if `path` ends in `.ttd`, as a table if it ends in `.csv` or `.tsv`,
+/// and as JSON otherwise.
+fn save(data: &TrigramData, path: &str) {
+    let res = match TableFormat::from_path(path) {
+        Some(format) => data.save_table(path, format),
+        None if path.ends_with(".ttd") => data.save_binary(path),
+        None => data.save(path),
+    };
+
+    if let Err(e) = res {
+        fail(format!("{path}: {e}"));
+    }
 }
 
-impl From<TrigramStatsInter> for TrigramStats {
-    fn from(stats: TrigramStatsInter) -> Self {
-        TrigramStats {
-            sfr: Avg::new(stats.sfr),
-            overall: Avg::new(stats.overall),
-            alternate: Avg::new(stats.alternate),
-            sfs: Avg::new(stats.sfs),
-            alternate_sfs: Avg::new(stats.alternate_sfs),
-            inroll: Avg::new(stats.inroll),
-            outroll: Avg::new(stats.outroll),
-            onehand: Avg::new(stats.onehand),
-            redirect: Avg::new(stats.redirect),
-            redirect_sfs: Avg::new(stats.redirect_sfs),
-            bad_redirect: Avg::new(stats.bad_redirect),
-            bad_redirect_sfs: Avg::new(stats.bad_redirect_sfs),
-            sfb: Avg::new(stats.sfb),
-            bad_sfb: Avg::new(stats.bad_sfb),
-            sft: Avg::new(stats.sft),
-            _other: Avg::new(stats.other),
-            _invalid: Avg::new(stats.invalid),
+fn main() {
+    let mut args = std::env::args().skip(1).peekable();
+
+    let command =
+        args.next_if(|arg| ["convert", "validate", "diff", "redact"].contains(&arg.as_str()));
+    let convert = command.as_deref() == Some("convert");
+    let redact = command.as_deref() == Some("redact");
+    let validate = command.as_deref() == Some("validate");
+    let diff = command.as_deref() == Some("diff");
+
+    let mut options = LoadOptions::default();
+    let mut verbose = false;
+    let mut save_path = None;
+    let mut summary_path = None;
+    let mut by_source = false;
+    let mut events = false;
+    let mut reconstructor = Reconstructor::default();
+    let mut validate_options = ValidateOptions::default();
+    let mut outliers = OutlierPolicy::None;
+    let mut threshold = 20.0;
+    let mut redact_options = RedactOptions::default();
+    let mut keymap = None;
+    let mut projection = Projection::default();
+    let mut modifiers = ModifierPolicy::default();
+    let mut keyboard_mod = None;
+    let mut import_options = ImportOptions::default();
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lenient" => options.mode = LoadMode::Lenient,
+            "--include" => options.filter.include.push(flag_value(&mut args, &arg)),
+            "--exclude" => options.filter.exclude.push(flag_value(&mut args, &arg)),
+            "--verbose" => verbose = true,
+            "--by-source" => by_source = true,
+            "--summary" => summary_path = Some(flag_value(&mut args, &arg)),
+            "--events" => events = true,
+            "--max-delay" => match flag_value(&mut args, &arg).parse() {
+                Ok(delay) => reconstructor.max_delay = Some(delay),
+                _ => fail("--max-delay needs a delay in ms"),
+            },
+            "--min-duration" | "--max-duration" => {
+                let duration = flag_value(&mut args, &arg)
+                    .parse()
+                    .ok()
+                    .and_then(Micros::from_ms)
+                    .unwrap_or_else(|| fail(format!("{arg} needs a duration in ms")));
+                match arg.as_str() {
+                    "--min-duration" => validate_options.min_duration = duration,
+                    _ => validate_options.max_duration = duration,
+                }
+            }
+            "--max-samples" => match flag_value(&mut args, &arg).parse() {
+                Ok(max) => validate_options.max_samples = max,
+                _ => fail("--max-samples needs a number"),
+            },
+            "--outliers" => match flag_value(&mut args, &arg).parse() {
+                Ok(policy) => outliers = policy,
+                Err(e) => fail(e),
+            },
+            "--threshold" => match flag_value(&mut args, &arg).parse::<f64>() {
+                Ok(ms) if ms >= 0.0 => threshold = ms,
+                _ => fail("--threshold needs a duration in ms"),
+            },
+            "--min-samples" => match flag_value(&mut args, &arg).parse() {
+                Ok(k) => redact_options.min_samples = k,
+                _ => fail("--min-samples needs a number"),
+            },
+            "--letters-only" => redact_options.letters_only = true,
+            "--keep-typist" => redact_options.keep_typist = true,
+            "--rekey" => match flag_value(&mut args, &arg).parse() {
+                Ok(class) => redact_options.rekey = Some(class),
+                Err(e) => fail(e),
+            },
+            "--projection" => match Projection::load(flag_value(&mut args, &arg)) {
+                Ok(p) => projection = p,
+                Err(e) => fail(e),
+            },
+            "--keymap" => keymap = Some(flag_value(&mut args, &arg)),
+            "--keymap-columns" => match flag_value(&mut args, &arg).parse() {
+                Ok(columns) => import_options.columns = Some(columns),
+                _ => fail("--keymap-columns needs a number"),
+            },
+            "--keymap-skip-rows" => match flag_value(&mut args, &arg).parse() {
+                Ok(rows) => import_options.skip_rows = Some(rows),
+                _ => fail("--keymap-skip-rows needs a number"),
+            },
+            "--via-definition" => {
+                import_options.via_definition = Some(flag_value(&mut args, &arg).into())
+            }
+            "--mod" => match flag_value(&mut args, &arg).parse() {
+                Ok(m) => keyboard_mod = Some(m),
+                Err(e) => fail(e),
+            },
+            "--modifiers" => match flag_value(&mut args, &arg).parse() {
+                Ok(policy) => modifiers = policy,
+                Err(e) => fail(e),
+            },
+            "--save" => save_path = Some(flag_value(&mut args, &arg)),
+            flag if flag.starts_with("--") => fail(format!("unknown flag {flag}")),
+            _ => paths.push(arg),
         }
     }
-}
 
-#[derive(Debug, Default, Clone)]
-pub struct TrigramStats {
-    overall: Avg,
-    sfb: Avg,
-    sfr: Avg,
-    sfs: Avg,
-    bad_sfb: Avg,
-    sft: Avg,
-    alternate: Avg,
-    alternate_sfs: Avg,
-    inroll: Avg,
-    outroll: Avg,
-    onehand: Avg,
-    redirect: Avg,
-    redirect_sfs: Avg,
-    bad_redirect: Avg,
-    bad_redirect_sfs: Avg,
-    _other: Avg,
-    _invalid: Avg,
-}
+    if modifiers == ModifierPolicy::Fold && !events {
+        fail("--modifiers fold only works with --events, trigram files can't be folded");
+    }
 
-impl std::fmt::Display for TrigramStats {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            concat!(
-                "Overall:        {}\n\n",
-                "Sfb:            {}\n",
-                "BadSfb:         {}\n",
-                "Sft:            {}\n",
-                "Sfr:            {}\n",
-                "Sfs:            {}\n\n",
-                "Alternate:      {}\n",
-                "Alternate Sfs:  {}\n\n",
-                "Inroll:         {}\n",
-                "Outroll:        {}\n",
-                "Onehand:        {}\n\n",
-                "Redirect:       {}\n",
-                "RedirectSfs:    {}\n",
-                "BadRedirect:    {}\n",
-                "BadRedirectSfs: {}\n\n",
-                // "Other:          {}\n",
-                // "Invalid:        {}\n",
-            ),
-            self.overall,
-            self.sfb,
-            self.bad_sfb,
-            self.sft,
-            self.sfr,
-            self.sfs,
-            self.alternate,
-            self.alternate_sfs,
-            self.inroll,
-            self.outroll,
-            self.onehand,
-            self.redirect,
-            self.redirect_sfs,
-            self.bad_redirect,
-            self.bad_redirect_sfs,
-            // self.other,
-            // self.invalid,
-        )
+    let keymap =
+        keymap.map(|path| KeyMap::load_with(path, &import_options).unwrap_or_else(|e| fail(e)));
+
+    if diff {
+        let [before, after] = &paths[..] else {
+            fail("diff needs two datasets to compare");
+        };
+        let [before, after] =
+            [before, after].map(
+                |path| match TrigramData::load_multiple_with(&[path], &options) {
+                    Ok((data, report)) => {
+                        print_report(&report, verbose);
+                        data
+                    }
+                    Err(e) => fail(e),
+                },
+            );
+        let project = |mut data: TrigramData| {
+            if keyboard_mod.is_some() {
+                data.metadata.keyboard_mod = keyboard_mod;
+            }
+            let keymap = keymap_for(keymap.clone(), &data.metadata);
+            let (data, _) = data
+                .project(projection.clone(), keymap, modifiers)
+                .unwrap_or_else(|e| fail(e));
+            data
+        };
+        print!("{}", before.diff(after, threshold, project));
+        return;
     }
-}
 
-fn finger(index: usize) -> usize {
-    // match (index / 10, index % 10) {
-    //     (3, 0) => Finger::LT,
-    //     (_, 0) => Finger::LP,
-    //     (_, 1) => Finger::LR,
-    //     (_, 2) => Finger::LM,
-    //     (_, 3) | (_, 4) => Finger::LI,
-    //     (_, 5) | (_, 6)=> Finger::RI,
-    //     (_, 7) => Finger::RM,
-    //     (_, 8) => Finger::RR,
-    //     (_, 9) => Finger::RP,
-    //     _ => unreachable!()
-    // }
-    match index % 10 {
-        n @ (0 | 1 | 2 | 3) => n,
-        n @ (4 | 5) => n - 1,
-        n @ (6 | 7 | 8 | 9) => n - 2,
-        _ => unreachable!(),
+    reconstructor.modifiers = modifiers;
+
+    let inputs = match convert || redact {
+        true if paths.len() >= 2 => &paths[..paths.len() - 1],
+        true => fail(format!(
+            "{} needs an input and an output path",
+            command.unwrap_or_default()
+        )),
+        false => &paths[..],
+    };
+
+    if events && options.filter.include.is_empty() {
+        options.filter.include.push("*.jsonl".to_string());
     }
-}
 
-fn fingers<const N: usize>(indexes: &[usize; N]) -> [usize; N] {
-    indexes
-        .into_iter()
-        .map(|f| finger(*f))
-        .collect::<Vec<_>>()
-        .try_into()
-        .unwrap()
-}
+    if redact && events {
+        let metadata = DatasetMetadata {
+            keyboard_mod,
+            ..Default::default()
+        };
+        redact_options.keymap = Some(keymap_for(keymap, &metadata));
+        let output = &paths[paths.len() - 1];
+        let (log, report) = load_event_log(inputs, &options.filter).redact(&redact_options);
+        eprintln!("{report}");
+        if let Err(e) = log.save(output) {
+            fail(format!("{output}: {e}"));
+        }
+        return;
+    }
 
-fn fingers_are_sfs([a, b, c]: &[usize; 3]) -> bool {
-    a == c && a != b
-}
+    let loaded = match events {
+        true => load_events(inputs, &options.filter, reconstructor),
+        false => TrigramData::load_multiple_with(inputs, &options),
+    };
 
-fn indexes_are_sfr([a, b, c]: &[usize; 3]) -> bool {
-    a == b || b == c
-}
+    let mut data = match loaded {
+        Ok((data, report)) => {
+            print_report(&report, verbose);
+            data
+        }
+        Err(e) => fail(e),
+    };
 
-impl MatrixData {
-    fn stats(&self) -> TrigramStats {
-        let mut inter = TrigramStatsInter::default();
+    if keyboard_mod.is_some() {
+        data.metadata.keyboard_mod = keyboard_mod;
+    }
 
-        for (indexes, vals) in self.0.iter() {
-            if indexes_are_sfr(&indexes) {
-                inter.sfr.extend(vals);
-                inter.overall.extend(vals);
-                continue;
-            }
+    if let Some(path) = &save_path {
+        save(&data, path);
+    }
+
+    if let Some(path) = &summary_path {
+        let format = TableFormat::from_path(path).unwrap_or(TableFormat::Csv);
+        if let Err(e) = data.save_summary(path, format) {
+            fail(format!("{path}: {e}"));
+        }
+    }
 
-            let [a, b, c] = fingers(indexes);
+    if convert {
+        save(&data, &paths[paths.len() - 1]);
+        return;
+    }
 
-            if fingers_are_sfs(&[a, b, c]) {
-                inter.sfs.extend(vals)
-            }
+    if redact {
+        redact_options.keymap = Some(keymap_for(keymap, &data.metadata));
+        let (data, report) = data.redact(&redact_options);
+        eprintln!("{report}");
+        save(&data, &paths[paths.len() - 1]);
+        return;
+    }
 
-            inter.overall.extend(vals);
-
-            let combination = (a << 6) | (b << 3) | c;
-            let pattern = TRIGRAM_COMBINATIONS[combination];
-
-            use trigram_patterns::TrigramPattern as T;
-
-            match pattern {
-                T::Alternate => inter.alternate.extend(vals),
-                T::AlternateSfs => inter.alternate_sfs.extend(vals),
-                T::Inroll => inter.inroll.extend(vals),
-                T::Outroll => inter.outroll.extend(vals),
-                T::Onehand => inter.onehand.extend(vals),
-                T::Redirect => inter.redirect.extend(vals),
-                T::RedirectSfs => inter.redirect_sfs.extend(vals),
-                T::BadRedirect => inter.bad_redirect.extend(vals),
-                T::BadRedirectSfs => inter.bad_redirect_sfs.extend(vals),
-                T::Sfb => inter.sfb.extend(vals),
-                T::BadSfb => inter.bad_sfb.extend(vals),
-                T::Sft => inter.sft.extend(vals),
-                T::Other => inter.other.extend(vals),
-            }
+    if validate {
+        validate_options.keymap = Some(keymap_for(keymap, &data.metadata));
+        let report = data.validate(&validate_options);
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => fail(e),
+        }
+        eprintln!("{} errors, {} warnings", report.errors, report.warnings);
+        if report.has_errors() {
+            std::process::exit(1);
         }
+        return;
+    }
 
-        inter.into()
+    let keymap = keymap_for(keymap, &data.metadata);
+    let (mut data, audit) = data
+        .project(projection, keymap, modifiers)
+        .unwrap_or_else(|e| fail(e));
+    let outlier_report = data.reject_outliers(&outliers);
+
+    if !data.metadata.is_empty() {
+        println!("{}", data.metadata);
     }
-}
 
-fn main() {
-    let paths = std::env::args().skip(1).collect::<Vec<_>>();
+    println!("{audit}");
 
-    let data = TrigramData::load_multiple(&paths).unwrap().matrix_3x10();
+    if outliers != OutlierPolicy::None {
+        println!("{outlier_report}");
+    }
 
     println!("{}", data.stats());
+
+    if by_source {
+        for (source, stats) in data.stats_by_source() {
+            println!("{source}\n\n{stats}");
+        }
+    }
 }
@@ -6,144 +6,328 @@ pub struct Pos {
     pub col: usize,
 }
 
+/// Every `KeyboardEvent.code` in the UI Events spec, along with F13 through F24, and the position
+/// it's stored as. Rows 0 through 4 follow the rows of a standard keyboard, higher rows hold keys
+/// outside the main block. Codes and positions are both unique, so parsing and displaying are each
+/// other's inverse; this is checked at compile time below.
+pub const CODES: &[(&str, Pos)] = &[
+    // Writing system and functional keys, by row of a standard keyboard.
+    ("Backquote", Pos { row: 0, col: 0 }),
+    ("Digit1", Pos { row: 0, col: 1 }),
+    ("Digit2", Pos { row: 0, col: 2 }),
+    ("Digit3", Pos { row: 0, col: 3 }),
+    ("Digit4", Pos { row: 0, col: 4 }),
+    ("Digit5", Pos { row: 0, col: 5 }),
+    ("Digit6", Pos { row: 0, col: 6 }),
+    ("Digit7", Pos { row: 0, col: 7 }),
+    ("Digit8", Pos { row: 0, col: 8 }),
+    ("Digit9", Pos { row: 0, col: 9 }),
+    ("Digit0", Pos { row: 0, col: 10 }),
+    ("Minus", Pos { row: 0, col: 11 }),
+    ("Equal", Pos { row: 0, col: 12 }),
+    ("Backspace", Pos { row: 0, col: 13 }),
+    ("IntlYen", Pos { row: 0, col: 14 }),
+    ("Tab", Pos { row: 1, col: 0 }),
+    ("KeyQ", Pos { row: 1, col: 1 }),
+    ("KeyW", Pos { row: 1, col: 2 }),
+    ("KeyE", Pos { row: 1, col: 3 }),
+    ("KeyR", Pos { row: 1, col: 4 }),
+    ("KeyT", Pos { row: 1, col: 5 }),
+    ("KeyY", Pos { row: 1, col: 6 }),
+    ("KeyU", Pos { row: 1, col: 7 }),
+    ("KeyI", Pos { row: 1, col: 8 }),
+    ("KeyO", Pos { row: 1, col: 9 }),
+    ("KeyP", Pos { row: 1, col: 10 }),
+    ("BracketRight", Pos { row: 1, col: 11 }),
+    ("BracketLeft", Pos { row: 1, col: 12 }),
+    ("Enter", Pos { row: 1, col: 13 }),
+    ("CapsLock", Pos { row: 2, col: 0 }),
+    ("KeyA", Pos { row: 2, col: 1 }),
+    ("KeyS", Pos { row: 2, col: 2 }),
+    ("KeyD", Pos { row: 2, col: 3 }),
+    ("KeyF", Pos { row: 2, col: 4 }),
+    ("KeyG", Pos { row: 2, col: 5 }),
+    ("KeyH", Pos { row: 2, col: 6 }),
+    ("KeyJ", Pos { row: 2, col: 7 }),
+    ("KeyK", Pos { row: 2, col: 8 }),
+    ("KeyL", Pos { row: 2, col: 9 }),
+    ("Semicolon", Pos { row: 2, col: 10 }),
+    ("Quote", Pos { row: 2, col: 11 }),
+    ("Backslash", Pos { row: 2, col: 12 }),
+    ("ShiftLeft", Pos { row: 3, col: 0 }),
+    ("IntlBackslash", Pos { row: 3, col: 1 }),
+    ("KeyZ", Pos { row: 3, col: 2 }),
+    ("KeyX", Pos { row: 3, col: 3 }),
+    ("KeyC", Pos { row: 3, col: 4 }),
+    ("KeyV", Pos { row: 3, col: 5 }),
+    ("KeyB", Pos { row: 3, col: 6 }),
+    ("KeyN", Pos { row: 3, col: 7 }),
+    ("KeyM", Pos { row: 3, col: 8 }),
+    ("Comma", Pos { row: 3, col: 9 }),
+    ("Period", Pos { row: 3, col: 10 }),
+    ("Slash", Pos { row: 3, col: 11 }),
+    ("ShiftRight", Pos { row: 3, col: 12 }),
+    ("IntlRo", Pos { row: 3, col: 13 }),
+    ("ControlLeft", Pos { row: 4, col: 0 }),
+    ("MetaLeft", Pos { row: 4, col: 1 }),
+    ("AltLeft", Pos { row: 4, col: 2 }),
+    ("Space", Pos { row: 4, col: 3 }),
+    ("AltRight", Pos { row: 4, col: 4 }),
+    ("ContextMenu", Pos { row: 4, col: 5 }),
+    ("ControlRight", Pos { row: 4, col: 6 }),
+    ("MetaRight", Pos { row: 4, col: 7 }),
+    ("NonConvert", Pos { row: 4, col: 8 }),
+    ("Convert", Pos { row: 4, col: 9 }),
+    ("KanaMode", Pos { row: 4, col: 10 }),
+    ("Lang1", Pos { row: 4, col: 11 }),
+    ("Lang2", Pos { row: 4, col: 12 }),
+    ("Lang3", Pos { row: 4, col: 13 }),
+    ("Lang4", Pos { row: 4, col: 14 }),
+    ("Lang5", Pos { row: 4, col: 15 }),
+    ("Fn", Pos { row: 4, col: 16 }),
+    ("FnLock", Pos { row: 4, col: 17 }),
+    // Control pad and arrow keys.
+    ("Insert", Pos { row: 5, col: 0 }),
+    ("Delete", Pos { row: 5, col: 1 }),
+    ("Home", Pos { row: 5, col: 2 }),
+    ("End", Pos { row: 5, col: 3 }),
+    ("PageUp", Pos { row: 5, col: 4 }),
+    ("PageDown", Pos { row: 5, col: 5 }),
+    ("Help", Pos { row: 5, col: 6 }),
+    ("ArrowUp", Pos { row: 5, col: 7 }),
+    ("ArrowLeft", Pos { row: 5, col: 8 }),
+    ("ArrowDown", Pos { row: 5, col: 9 }),
+    ("ArrowRight", Pos { row: 5, col: 10 }),
+    // Numpad.
+    ("NumLock", Pos { row: 6, col: 0 }),
+    ("Numpad0", Pos { row: 6, col: 1 }),
+    ("Numpad1", Pos { row: 6, col: 2 }),
+    ("Numpad2", Pos { row: 6, col: 3 }),
+    ("Numpad3", Pos { row: 6, col: 4 }),
+    ("Numpad4", Pos { row: 6, col: 5 }),
+    ("Numpad5", Pos { row: 6, col: 6 }),
+    ("Numpad6", Pos { row: 6, col: 7 }),
+    ("Numpad7", Pos { row: 6, col: 8 }),
+    ("Numpad8", Pos { row: 6, col: 9 }),
+    ("Numpad9", Pos { row: 6, col: 10 }),
+    ("NumpadAdd", Pos { row: 6, col: 11 }),
+    ("NumpadSubtract", Pos { row: 6, col: 12 }),
+    ("NumpadMultiply", Pos { row: 6, col: 13 }),
+    ("NumpadDivide", Pos { row: 6, col: 14 }),
+    ("NumpadDecimal", Pos { row: 6, col: 15 }),
+    ("NumpadEnter", Pos { row: 6, col: 16 }),
+    ("NumpadEqual", Pos { row: 6, col: 17 }),
+    ("NumpadComma", Pos { row: 6, col: 18 }),
+    ("NumpadBackspace", Pos { row: 6, col: 19 }),
+    ("NumpadClear", Pos { row: 6, col: 20 }),
+    ("NumpadClearEntry", Pos { row: 6, col: 21 }),
+    ("NumpadHash", Pos { row: 6, col: 22 }),
+    ("NumpadStar", Pos { row: 6, col: 23 }),
+    ("NumpadParenLeft", Pos { row: 6, col: 24 }),
+    ("NumpadParenRight", Pos { row: 6, col: 25 }),
+    ("NumpadMemoryAdd", Pos { row: 6, col: 26 }),
+    ("NumpadMemoryClear", Pos { row: 6, col: 27 }),
+    ("NumpadMemoryRecall", Pos { row: 6, col: 28 }),
+    ("NumpadMemoryStore", Pos { row: 6, col: 29 }),
+    ("NumpadMemorySubtract", Pos { row: 6, col: 30 }),
+    // Media keys.
+    ("BrowserBack", Pos { row: 7, col: 0 }),
+    ("BrowserFavorites", Pos { row: 7, col: 1 }),
+    ("BrowserForward", Pos { row: 7, col: 2 }),
+    ("BrowserHome", Pos { row: 7, col: 3 }),
+    ("BrowserRefresh", Pos { row: 7, col: 4 }),
+    ("BrowserSearch", Pos { row: 7, col: 5 }),
+    ("BrowserStop", Pos { row: 7, col: 6 }),
+    ("Eject", Pos { row: 7, col: 7 }),
+    ("LaunchApp1", Pos { row: 7, col: 8 }),
+    ("LaunchApp2", Pos { row: 7, col: 9 }),
+    ("LaunchMail", Pos { row: 7, col: 10 }),
+    ("MediaPlayPause", Pos { row: 7, col: 11 }),
+    ("MediaSelect", Pos { row: 7, col: 12 }),
+    ("MediaStop", Pos { row: 7, col: 13 }),
+    ("MediaTrackNext", Pos { row: 7, col: 14 }),
+    ("MediaTrackPrevious", Pos { row: 7, col: 15 }),
+    ("Power", Pos { row: 7, col: 16 }),
+    ("Sleep", Pos { row: 7, col: 17 }),
+    ("AudioVolumeDown", Pos { row: 7, col: 18 }),
+    ("AudioVolumeMute", Pos { row: 7, col: 19 }),
+    ("AudioVolumeUp", Pos { row: 7, col: 20 }),
+    ("WakeUp", Pos { row: 7, col: 21 }),
+    // Legacy keys and keys without a code.
+    ("Hyper", Pos { row: 8, col: 0 }),
+    ("Super", Pos { row: 8, col: 1 }),
+    ("Turbo", Pos { row: 8, col: 2 }),
+    ("Abort", Pos { row: 8, col: 3 }),
+    ("Resume", Pos { row: 8, col: 4 }),
+    ("Suspend", Pos { row: 8, col: 5 }),
+    ("Again", Pos { row: 8, col: 6 }),
+    ("Copy", Pos { row: 8, col: 7 }),
+    ("Cut", Pos { row: 8, col: 8 }),
+    ("Find", Pos { row: 8, col: 9 }),
+    ("Open", Pos { row: 8, col: 10 }),
+    ("Paste", Pos { row: 8, col: 11 }),
+    ("Props", Pos { row: 8, col: 12 }),
+    ("Select", Pos { row: 8, col: 13 }),
+    ("Undo", Pos { row: 8, col: 14 }),
+    ("Hiragana", Pos { row: 8, col: 15 }),
+    ("Katakana", Pos { row: 8, col: 16 }),
+    ("Unidentified", Pos { row: 8, col: 17 }),
+    // Function row.
+    ("Escape", Pos { row: 9, col: 0 }),
+    ("F1", Pos { row: 9, col: 1 }),
+    ("F2", Pos { row: 9, col: 2 }),
+    ("F3", Pos { row: 9, col: 3 }),
+    ("F4", Pos { row: 9, col: 4 }),
+    ("F5", Pos { row: 9, col: 5 }),
+    ("F6", Pos { row: 9, col: 6 }),
+    ("F7", Pos { row: 9, col: 7 }),
+    ("F8", Pos { row: 9, col: 8 }),
+    ("F9", Pos { row: 9, col: 9 }),
+    ("F10", Pos { row: 9, col: 10 }),
+    ("F11", Pos { row: 9, col: 11 }),
+    ("F12", Pos { row: 9, col: 12 }),
+    ("F13", Pos { row: 9, col: 13 }),
+    ("F14", Pos { row: 9, col: 14 }),
+    ("F15", Pos { row: 9, col: 15 }),
+    ("F16", Pos { row: 9, col: 16 }),
+    ("F17", Pos { row: 9, col: 17 }),
+    ("F18", Pos { row: 9, col: 18 }),
+    ("F19", Pos { row: 9, col: 19 }),
+    ("F20", Pos { row: 9, col: 20 }),
+    ("F21", Pos { row: 9, col: 21 }),
+    ("F22", Pos { row: 9, col: 22 }),
+    ("F23", Pos { row: 9, col: 23 }),
+    ("F24", Pos { row: 9, col: 24 }),
+    ("PrintScreen", Pos { row: 9, col: 25 }),
+    ("ScrollLock", Pos { row: 9, col: 26 }),
+    ("Pause", Pos { row: 9, col: 27 }),
+];
+
+/// Codes older browsers send instead of the ones in the spec, e.g. Firefox before version 118.
+pub const ALIASES: &[(&str, &str)] = &[
+    ("OSLeft", "MetaLeft"),
+    ("OSRight", "MetaRight"),
+    ("VolumeDown", "AudioVolumeDown"),
+    ("VolumeMute", "AudioVolumeMute"),
+    ("VolumeUp", "AudioVolumeUp"),
+];
+
+pub(crate) const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+pub(crate) const fn code_index(code: &str) -> Option<usize> {
+    let mut i = 0;
+    while i < CODES.len() {
+        if str_eq(CODES[i].0, code) {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+const _: () = {
+    let mut i = 0;
+    while i < CODES.len() {
+        let mut j = i + 1;
+        while j < CODES.len() {
+            let (a, b) = (CODES[i].1, CODES[j].1);
+            assert!(!str_eq(CODES[i].0, CODES[j].0), "duplicate code");
+            assert!(a.row != b.row || a.col != b.col, "duplicate position");
+            j += 1;
+        }
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < ALIASES.len() {
+        assert!(code_index(ALIASES[i].0).is_none(), "alias shadows a code");
+        assert!(
+            code_index(ALIASES[i].1).is_some(),
+            "alias of an unknown code"
+        );
+        i += 1;
+    }
+};
+
+impl Pos {
+    /// The `KeyboardEvent.code` of the key at this position, if there is one.
+    pub fn code(self) -> Option<&'static str> {
+        CODES
+            .iter()
+            .find(|(_, pos)| *pos == self)
+            .map(|(code, _)| *code)
+    }
+}
+
 impl FromStr for Pos {
     type Err = String;
 
     fn from_str(key: &str) -> Result<Self, Self::Err> {
-        match key {
-            "Escape" => Ok(Pos { row: 9, col: 0 }),
-            "Backquote" => Ok(Pos { row: 0, col: 0 }),
-            "Digit1" => Ok(Pos { row: 0, col: 1 }),
-            "Digit2" => Ok(Pos { row: 0, col: 2 }),
-            "Digit3" => Ok(Pos { row: 0, col: 3 }),
-            "Digit4" => Ok(Pos { row: 0, col: 4 }),
-            "Digit5" => Ok(Pos { row: 0, col: 5 }),
-            "Digit6" => Ok(Pos { row: 0, col: 6 }),
-            "Digit7" => Ok(Pos { row: 0, col: 7 }),
-            "Digit8" => Ok(Pos { row: 0, col: 8 }),
-            "Digit9" => Ok(Pos { row: 0, col: 9 }),
-            "Digit0" => Ok(Pos { row: 0, col: 10 }),
-            "Minus" => Ok(Pos { row: 0, col: 11 }),
-            "Equal" => Ok(Pos { row: 0, col: 12 }),
-            "Backspace" => Ok(Pos { row: 0, col: 13 }),
-            "Tab" => Ok(Pos { row: 1, col: 0 }),
-            "KeyQ" => Ok(Pos { row: 1, col: 1 }),
-            "KeyW" => Ok(Pos { row: 1, col: 2 }),
-            "KeyE" => Ok(Pos { row: 1, col: 3 }),
-            "KeyR" => Ok(Pos { row: 1, col: 4 }),
-            "KeyT" => Ok(Pos { row: 1, col: 5 }),
-            "KeyY" => Ok(Pos { row: 1, col: 6 }),
-            "KeyU" => Ok(Pos { row: 1, col: 7 }),
-            "KeyI" => Ok(Pos { row: 1, col: 8 }),
-            "KeyO" => Ok(Pos { row: 1, col: 9 }),
-            "KeyP" => Ok(Pos { row: 1, col: 10 }),
-            "BracketRight" => Ok(Pos { row: 1, col: 11 }),
-            "BracketLeft" => Ok(Pos { row: 1, col: 12 }),
-            "Enter" => Ok(Pos { row: 1, col: 13 }),
-            "CapsLock" => Ok(Pos { row: 2, col: 0 }),
-            "KeyA" => Ok(Pos { row: 2, col: 1 }),
-            "KeyS" => Ok(Pos { row: 2, col: 2 }),
-            "KeyD" => Ok(Pos { row: 2, col: 3 }),
-            "KeyF" => Ok(Pos { row: 2, col: 4 }),
-            "KeyG" => Ok(Pos { row: 2, col: 5 }),
-            "KeyH" => Ok(Pos { row: 2, col: 6 }),
-            "KeyJ" => Ok(Pos { row: 2, col: 7 }),
-            "KeyK" => Ok(Pos { row: 2, col: 8 }),
-            "KeyL" => Ok(Pos { row: 2, col: 9 }),
-            "Semicolon" => Ok(Pos { row: 2, col: 10 }),
-            "Quote" => Ok(Pos { row: 2, col: 11 }),
-            "Backslash" => Ok(Pos { row: 2, col: 12 }),
-            "ShiftLeft" => Ok(Pos { row: 3, col: 0 }),
-            "IntlBackslash" => Ok(Pos { row: 3, col: 1 }),
-            "KeyZ" => Ok(Pos { row: 3, col: 2 }),
-            "KeyX" => Ok(Pos { row: 3, col: 3 }),
-            "KeyC" => Ok(Pos { row: 3, col: 4 }),
-            "KeyV" => Ok(Pos { row: 3, col: 5 }),
-            "KeyB" => Ok(Pos { row: 3, col: 6 }),
-            "KeyN" => Ok(Pos { row: 3, col: 7 }),
-            "KeyM" => Ok(Pos { row: 3, col: 8 }),
-            "Comma" => Ok(Pos { row: 3, col: 9 }),
-            "Period" => Ok(Pos { row: 3, col: 10 }),
-            "Slash" => Ok(Pos { row: 3, col: 11 }),
-            "ShiftRight" => Ok(Pos { row: 3, col: 12 }),
-            "ControlLeft" => Ok(Pos { row: 4, col: 0 }),
-            "OSLeft" => Ok(Pos { row: 4, col: 1 }),
-            "AltLeft" => Ok(Pos { row: 4, col: 2 }),
-            "Space" => Ok(Pos { row: 4, col: 3 }),
-            "AltRight" => Ok(Pos { row: 4, col: 4 }),
-            "ContextMenu" => Ok(Pos { row: 4, col: 5 }),
-            "ControlRight" => Ok(Pos { row: 4, col: 6 }),
-            _ => Err(format!("invalid key: {}", key)),
+        let key = ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == key)
+            .map_or(key, |(_, code)| code);
+
+        match code_index(key) {
+            Some(i) => Ok(CODES[i].1),
+            None => Err(format!("invalid key: {}", key)),
         }
     }
 }
 
+/// Formats a trigram the way datasets key it, like `KeyA,KeyB,KeyC`.
+pub fn trigram_str([a, b, c]: &[Pos; 3]) -> String {
+    format!("{a},{b},{c}")
+}
+
+/// Writes the code of the key, or `Unknown(row:col)` for positions no key is stored as.
 impl Display for Pos {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Pos { row: 9, col: 0 } => write!(f, "Escape"),
-            Pos { row: 0, col: 0 } => write!(f, "Backquote"),
-            Pos { row: 0, col: 1 } => write!(f, "Digit1"),
-            Pos { row: 0, col: 2 } => write!(f, "Digit2"),
-            Pos { row: 0, col: 3 } => write!(f, "Digit3"),
-            Pos { row: 0, col: 4 } => write!(f, "Digit4"),
-            Pos { row: 0, col: 5 } => write!(f, "Digit5"),
-            Pos { row: 0, col: 6 } => write!(f, "Digit6"),
-            Pos { row: 0, col: 7 } => write!(f, "Digit7"),
-            Pos { row: 0, col: 8 } => write!(f, "Digit8"),
-            Pos { row: 0, col: 9 } => write!(f, "Digit9"),
-            Pos { row: 0, col: 10 } => write!(f, "Digit0"),
-            Pos { row: 0, col: 11 } => write!(f, "Minus"),
-            Pos { row: 0, col: 12 } => write!(f, "Equal"),
-            Pos { row: 0, col: 13 } => write!(f, "Backspace"),
-            Pos { row: 1, col: 0 } => write!(f, "Tab"),
-            Pos { row: 1, col: 1 } => write!(f, "KeyQ"),
-            Pos { row: 1, col: 2 } => write!(f, "KeyW"),
-            Pos { row: 1, col: 3 } => write!(f, "KeyE"),
-            Pos { row: 1, col: 4 } => write!(f, "KeyR"),
-            Pos { row: 1, col: 5 } => write!(f, "KeyT"),
-            Pos { row: 1, col: 6 } => write!(f, "KeyY"),
-            Pos { row: 1, col: 7 } => write!(f, "KeyU"),
-            Pos { row: 1, col: 8 } => write!(f, "KeyI"),
-            Pos { row: 1, col: 9 } => write!(f, "KeyO"),
-            Pos { row: 1, col: 10 } => write!(f, "KeyP"),
-            Pos { row: 1, col: 11 } => write!(f, "BracketRight"),
-            Pos { row: 1, col: 12 } => write!(f, "BracketLeft"),
-            Pos { row: 1, col: 13 } => write!(f, "Enter"),
-            Pos { row: 2, col: 0 } => write!(f, "CapsLock"),
-            Pos { row: 2, col: 1 } => write!(f, "KeyA"),
-            Pos { row: 2, col: 2 } => write!(f, "KeyS"),
-            Pos { row: 2, col: 3 } => write!(f, "KeyD"),
-            Pos { row: 2, col: 4 } => write!(f, "KeyF"),
-            Pos { row: 2, col: 5 } => write!(f, "KeyG"),
-            Pos { row: 2, col: 6 } => write!(f, "KeyH"),
-            Pos { row: 2, col: 7 } => write!(f, "KeyJ"),
-            Pos { row: 2, col: 8 } => write!(f, "KeyK"),
-            Pos { row: 2, col: 9 } => write!(f, "KeyL"),
-            Pos { row: 2, col: 10 } => write!(f, "Semicolon"),
-            Pos { row: 2, col: 11 } => write!(f, "Quote"),
-            Pos { row: 2, col: 12 } => write!(f, "Backslash"),
-            Pos { row: 3, col: 0 } => write!(f, "ShiftLeft"),
-            Pos { row: 3, col: 1 } => write!(f, "IntlBackslash"),
-            Pos { row: 3, col: 2 } => write!(f, "KeyZ"),
-            Pos { row: 3, col: 3 } => write!(f, "KeyX"),
-            Pos { row: 3, col: 4 } => write!(f, "KeyC"),
-            Pos { row: 3, col: 5 } => write!(f, "KeyV"),
-            Pos { row: 3, col: 6 } => write!(f, "KeyB"),
-            Pos { row: 3, col: 7 } => write!(f, "KeyN"),
-            Pos { row: 3, col: 8 } => write!(f, "KeyM"),
-            Pos { row: 3, col: 9 } => write!(f, "Comma"),
-            Pos { row: 3, col: 10 } => write!(f, "Period"),
-            Pos { row: 3, col: 11 } => write!(f, "Slash"),
-            Pos { row: 3, col: 12 } => write!(f, "ShiftRight"),
-            Pos { row: 4, col: 0 } => write!(f, "ControlLeft"),
-            Pos { row: 4, col: 1 } => write!(f, "OSLeft"),
-            Pos { row: 4, col: 2 } => write!(f, "AltLeft"),
-            Pos { row: 4, col: 3 } => write!(f, "Space"),
-            Pos { row: 4, col: 4 } => write!(f, "AltRight"),
-            Pos { row: 4, col: 5 } => write!(f, "ContextMenu"),
-            Pos { row: 4, col: 6 } => write!(f, "ControlRight"),
-            _ => unreachable!("Invalid keyboard position: {self:?}"),
+        match self.code() {
+            Some(code) => write!(f, "{code}"),
+            None => write!(f, "Unknown({}:{})", self.row, self.col),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_code_round_trips() {
+        for (code, pos) in CODES {
+            let parsed = code.parse::<Pos>().unwrap();
+            assert_eq!(parsed, *pos, "{code}");
+            assert_eq!(parsed.to_string(), *code);
+        }
+    }
+
+    #[test]
+    fn aliases_parse_to_their_code() {
+        for (alias, code) in ALIASES {
+            assert_eq!(alias.parse::<Pos>().unwrap().to_string(), *code, "{alias}");
         }
     }
+
+    #[test]
+    fn unknown_positions_display() {
+        let pos = Pos { row: 200, col: 7 };
+        assert_eq!(pos.code(), None);
+        assert_eq!(pos.to_string(), "Unknown(200:7)");
+        assert!("NotAKey".parse::<Pos>().is_err());
+    }
 }
@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{geometry::Board, mods::KeyboardMod};
+
+/// Newest envelope version this crate knows how to read.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Information about how and by whom a dataset was collected. Every field is optional because
+/// legacy bare-map files carry none of it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatasetMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyboard: Option<String>,
+    /// How the typist's hands sit on a row staggered `keyboard`, see [`KeyboardMod`].
+    #[serde(rename = "mod", skip_serializing_if = "Option::is_none")]
+    pub keyboard_mod: Option<KeyboardMod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// First and last day of collection as ISO 8601 dates, e.g. `["2023-04-01", "2023-04-12"]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_range: Option<[String; 2]>,
+    /// The `max_trigram_delay` the collector used, in ms. Samples above it were never recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_delay: Option<u16>,
+}
+
+impl DatasetMetadata {
+    /// Merges the metadata of two datasets that are about to be combined.
+    ///
+    /// Datasets recorded on different keyboards, with different mods or with different delay
+    /// thresholds measure different things and are refused. Differing typists, layouts or sources
+    /// are allowed, but the field no longer describes the result so it is cleared and a warning is
+    /// returned.
+    pub fn merge(self, other: Self) -> Result<(Self, Vec<String>), MetadataConflict> {
+        let mut warnings = Vec::new();
+
+        let keyboard = merge_strict("keyboard", self.keyboard, other.keyboard)?;
+        let keyboard_mod = merge_strict("mod", self.keyboard_mod, other.keyboard_mod)?;
+        let max_delay = merge_strict("max_delay", self.max_delay, other.max_delay)?;
+
+        let typist = merge_loose("typist", self.typist, other.typist, &mut warnings);
+        let layout = merge_loose("layout", self.layout, other.layout, &mut warnings);
+        let source = merge_loose("source", self.source, other.source, &mut warnings);
+
+        let date_range = match (self.date_range, other.date_range) {
+            (Some([s1, e1]), Some([s2, e2])) => Some([s1.min(s2), e1.max(e2)]),
+            (a, b) => a.or(b),
+        };
+
+        let merged = Self {
+            typist,
+            layout,
+            keyboard,
+            keyboard_mod,
+            source,
+            date_range,
+            max_delay,
+        };
+
+        Ok((merged, warnings))
+    }
+
+    /// The board named by `keyboard`, or ANSI if there isn't one.
+    pub fn board(&self) -> Board {
+        self.keyboard
+            .as_deref()
+            .and_then(|keyboard| keyboard.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Two datasets disagree on a field that makes them incomparable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetadataConflict {
+    pub field: &'static str,
+    pub left: String,
+    pub right: String,
+}
+
+impl std::fmt::Display for MetadataConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "refusing to combine datasets with different {}: {} and {}",
+            self.field, self.left, self.right
+        )
+    }
+}
+
+fn merge_strict<T: PartialEq + std::fmt::Display>(
+    field: &'static str,
+    a: Option<T>,
+    b: Option<T>,
+) -> Result<Option<T>, MetadataConflict> {
+    match (a, b) {
+        (Some(a), Some(b)) if a != b => Err(MetadataConflict {
+            field,
+            left: a.to_string(),
+            right: b.to_string(),
+        }),
+        (a, b) => Ok(a.or(b)),
+    }
+}
+
+fn merge_loose(
+    field: &str,
+    a: Option<String>,
+    b: Option<String>,
+    warnings: &mut Vec<String>,
+) -> Option<String> {
+    match (a, b) {
+        (Some(a), Some(b)) if a != b => {
+            warnings.push(format!(
+                "combining datasets with different {field}: {a} and {b}"
+            ));
+            None
+        }
+        (a, b) => a.or(b),
+    }
+}
+
+impl std::fmt::Display for DatasetMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = [
+            ("Typist", self.typist.clone()),
+            ("Layout", self.layout.clone()),
+            ("Keyboard", self.keyboard.clone()),
+            ("Mod", self.keyboard_mod.map(|m| m.to_string())),
+            ("Source", self.source.clone()),
+            (
+                "Dates",
+                self.date_range.as_ref().map(|[s, e]| format!("{s} to {e}")),
+            ),
+            ("Max delay", self.max_delay.map(|d| format!("{d} ms"))),
+        ];
+
+        for (name, value) in fields {
+            if let Some(value) = value {
+                writeln!(f, "{:<16}{}", format!("{name}:"), value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(typist: &str, keyboard: &str, max_delay: u16) -> DatasetMetadata {
+        DatasetMetadata {
+            typist: Some(typist.to_string()),
+            keyboard: Some(keyboard.to_string()),
+            max_delay: Some(max_delay),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn strict_fields_conflict_and_loose_fields_are_cleared() {
+        let conflict = metadata("maya", "ansi", 750)
+            .merge(metadata("maya", "iso", 750))
+            .unwrap_err();
+        assert_eq!(conflict.field, "keyboard");
+        assert_eq!(
+            (conflict.left.as_str(), conflict.right.as_str()),
+            ("ansi", "iso")
+        );
+
+        let conflict = metadata("maya", "ansi", 750)
+            .merge(metadata("maya", "ansi", 500))
+            .unwrap_err();
+        assert_eq!(conflict.field, "max_delay");
+
+        let angle = DatasetMetadata {
+            keyboard_mod: Some(KeyboardMod::Angle),
+            ..Default::default()
+        };
+        let wide = DatasetMetadata {
+            keyboard_mod: Some(KeyboardMod::Wide),
+            ..Default::default()
+        };
+        assert_eq!(angle.merge(wide).unwrap_err().field, "mod");
+
+        let (merged, warnings) = metadata("maya", "ansi", 750)
+            .merge(metadata("sam", "ansi", 750))
+            .unwrap();
+        assert_eq!(merged.typist, None);
+        assert_eq!(merged.keyboard.as_deref(), Some("ansi"));
+        assert_eq!(
+            warnings,
+            ["combining datasets with different typist: maya and sam"]
+        );
+    }
+
+    #[test]
+    fn missing_fields_are_taken_from_the_other_side() {
+        let a = DatasetMetadata {
+            layout: Some("dvorak".to_string()),
+            date_range: Some(["2023-04-03".to_string(), "2023-04-12".to_string()]),
+            ..Default::default()
+        };
+        let b = DatasetMetadata {
+            keyboard: Some("iso".to_string()),
+            date_range: Some(["2023-04-01".to_string(), "2023-04-05".to_string()]),
+            ..Default::default()
+        };
+
+        let (merged, warnings) = a.merge(b).unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(merged.layout.as_deref(), Some("dvorak"));
+        assert_eq!(merged.board(), Board::Iso);
+        assert_eq!(
+            merged.date_range,
+            Some(["2023-04-01".to_string(), "2023-04-12".to_string()])
+        );
+    }
+}
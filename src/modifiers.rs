@@ -0,0 +1,128 @@
+//! Shift and the other modifiers are held while another key is pressed, so they're chord state
+//! rather than keys of their own. The extension records them like any other key, which puts them
+//! in trigrams with the keys they modify.
+
+use std::str::FromStr;
+
+use crate::mapping::Pos;
+
+/// Keys that are chorded with others rather than typed on their own.
+pub const MODIFIERS: &[&str] = &[
+    "ShiftLeft",
+    "ShiftRight",
+    "ControlLeft",
+    "ControlRight",
+    "AltLeft",
+    "AltRight",
+    "MetaLeft",
+    "MetaRight",
+];
+
+pub fn is_modifier(pos: Pos) -> bool {
+    pos.code().is_some_and(|code| MODIFIERS.contains(&code))
+}
+
+/// What happens to trigrams typed while a modifier is held.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ModifierPolicy {
+    /// `Shift+X` counts as `X`, and the trigrams it was folded into are counted in the
+    /// [`ProjectionAudit`](crate::projection::ProjectionAudit). This needs the order of key events,
+    /// so it only exists for event logs, see [`Reconstructor`](crate::events::Reconstructor).
+    /// Trigram files record the modifier as a key of its own, and the trigram it was part of can't
+    /// be recovered, so projecting them with `Fold` fails with [`CantFold`].
+    Fold,
+    /// Trigrams containing a modifier are dropped.
+    #[default]
+    Drop,
+    /// Trigrams containing a modifier are reported as a category of their own.
+    Separate,
+}
+
+impl FromStr for ModifierPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fold" => Ok(Self::Fold),
+            "drop" => Ok(Self::Drop),
+            "separate" => Ok(Self::Separate),
+            _ => Err(format!(
+                "unknown modifier policy '{s}', expected fold, drop or separate"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ModifierPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fold => write!(f, "fold"),
+            Self::Drop => write!(f, "drop"),
+            Self::Separate => write!(f, "separate"),
+        }
+    }
+}
+
+/// Trigram data that wasn't reconstructed with [`ModifierPolicy::Fold`] can't be projected with it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CantFold;
+
+impl std::fmt::Display for CantFold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "modifiers can only be folded into trigrams reconstructed from an event log"
+        )
+    }
+}
+
+impl std::error::Error for CantFold {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        keymap::KeyMap,
+        projection::Projection,
+        testing::{fixture, shifted},
+    };
+
+    fn audit(modifiers: ModifierPolicy) -> crate::projection::ProjectionAudit {
+        let (_, audit) = shifted(modifiers)
+            .project(Projection::default(), KeyMap::default(), modifiers)
+            .unwrap();
+        audit
+    }
+
+    #[test]
+    fn every_policy_handles_shifted_keys() {
+        let fold = audit(ModifierPolicy::Fold);
+        assert_eq!((fold.kept.trigrams, fold.kept.samples), (3, 3));
+        assert_eq!((fold.folded.trigrams, fold.folded.samples), (2, 2));
+        assert_eq!(fold.dropped.trigrams, 0);
+
+        let drop = audit(ModifierPolicy::Drop);
+        assert_eq!(drop.kept.trigrams, 2);
+        assert_eq!(drop.folded.trigrams, 0);
+        assert_eq!(drop.dropped.trigrams, 3);
+        assert_eq!(drop.dropped_by_key["ShiftLeft"].trigrams, 1);
+        assert_eq!(drop.dropped_by_key["ShiftRight"].trigrams, 2);
+
+        let separate = audit(ModifierPolicy::Separate);
+        assert_eq!(separate.kept.trigrams, 2);
+        assert_eq!(separate.chorded.trigrams, 3);
+        assert_eq!(separate.dropped.trigrams, 0);
+    }
+
+    #[test]
+    fn only_folded_reconstructions_can_be_projected_with_fold() {
+        for data in [fixture(), shifted(ModifierPolicy::Drop)] {
+            let projected = data.project(
+                Projection::default(),
+                KeyMap::default(),
+                ModifierPolicy::Fold,
+            );
+            assert_eq!(projected.err(), Some(CantFold));
+        }
+    }
+}
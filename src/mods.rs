@@ -0,0 +1,204 @@
+//! Angle and wide mods, ways of typing on a row staggered board that move keys between fingers.
+//!
+//! With the angle mod the left hand's bottom row is typed at an angle, so each finger presses the
+//! key to the left of the usual one. With the wide mod the right hand sits one key further right,
+//! leaving the `KeyY` column unused. Both change which key is in which column of the matrix and
+//! which finger presses it, so a [`KeyMap`] has to be adjusted for them.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geometry::Board,
+    keymap::{KeyMap, MappedKey},
+    trigram_patterns::Finger,
+};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyboardMod {
+    #[default]
+    None,
+    Angle,
+    Wide,
+    AngleWide,
+}
+
+impl FromStr for KeyboardMod {
+    type Err = String;
+
+    /// Parses the names used for the `mod` metadata field.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "angle" => Ok(Self::Angle),
+            "wide" => Ok(Self::Wide),
+            "angle-wide" | "anglewide" => Ok(Self::AngleWide),
+            _ => Err(format!(
+                "unknown mod '{s}', expected none, angle, wide or angle-wide"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for KeyboardMod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::None => "none",
+            Self::Angle => "angle",
+            Self::Wide => "wide",
+            Self::AngleWide => "angle-wide",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A key moved by a mod, with the finger that presses it afterwards.
+type Moved = (&'static str, isize, isize, Finger);
+
+/// Angle mod on ANSI and JIS. The pinky loses its bottom row key, and the letter in its column is
+/// usually moved to `KeyB`.
+const ANGLE_ANSI: &[Moved] = &[
+    ("KeyZ", 2, 1, Finger::LR),
+    ("KeyX", 2, 2, Finger::LM),
+    ("KeyC", 2, 3, Finger::LI),
+    ("KeyV", 2, 4, Finger::LI),
+    ("KeyB", 2, 0, Finger::LI),
+];
+
+/// Angle mod on ISO, where the pinky types `IntlBackslash` instead and `KeyB` isn't used.
+const ANGLE_ISO: &[Moved] = &[
+    ("IntlBackslash", 2, 0, Finger::LP),
+    ("KeyZ", 2, 1, Finger::LR),
+    ("KeyX", 2, 2, Finger::LM),
+    ("KeyC", 2, 3, Finger::LI),
+    ("KeyV", 2, 4, Finger::LI),
+];
+const ANGLE_ISO_UNUSED: &[&str] = &["KeyB"];
+
+const WIDE: &[Moved] = &[
+    ("Digit7", -1, 5, Finger::RI),
+    ("Digit8", -1, 6, Finger::RI),
+    ("Digit9", -1, 7, Finger::RM),
+    ("Digit0", -1, 8, Finger::RR),
+    ("Minus", -1, 9, Finger::RP),
+    ("Equal", -1, 10, Finger::RP),
+    ("KeyU", 0, 5, Finger::RI),
+    ("KeyI", 0, 6, Finger::RI),
+    ("KeyO", 0, 7, Finger::RM),
+    ("KeyP", 0, 8, Finger::RR),
+    ("BracketLeft", 0, 9, Finger::RP),
+    ("BracketRight", 0, 10, Finger::RP),
+    ("KeyJ", 1, 5, Finger::RI),
+    ("KeyK", 1, 6, Finger::RI),
+    ("KeyL", 1, 7, Finger::RM),
+    ("Semicolon", 1, 8, Finger::RR),
+    ("Quote", 1, 9, Finger::RP),
+    ("KeyM", 2, 5, Finger::RI),
+    ("Comma", 2, 6, Finger::RI),
+    ("Period", 2, 7, Finger::RM),
+    ("Slash", 2, 8, Finger::RR),
+];
+const WIDE_UNUSED: &[&str] = &["Digit6", "KeyY", "KeyH", "KeyN"];
+
+/// Wide mod on JIS, where `IntlRo` moves into the column `Slash` leaves.
+const WIDE_JIS: &[Moved] = &[("IntlRo", 2, 9, Finger::RP)];
+
+impl KeyMap {
+    /// Moves keys according to `keyboard_mod`, as typed on `board`. Mods only exist for row
+    /// staggered boards, maps for ortholinear and column staggered boards are returned as is.
+    pub fn with_mod(mut self, keyboard_mod: KeyboardMod, board: Board) -> Self {
+        let (angle, wide) = match keyboard_mod {
+            KeyboardMod::None => return self,
+            KeyboardMod::Angle => (true, false),
+            KeyboardMod::Wide => (false, true),
+            KeyboardMod::AngleWide => (true, true),
+        };
+        if matches!(board, Board::Ortho | Board::Colstag) {
+            return self;
+        }
+
+        let mut moved = Vec::<Moved>::new();
+        let mut unused = Vec::<&str>::new();
+        if angle && board == Board::Iso {
+            moved.extend(ANGLE_ISO);
+            unused.extend(ANGLE_ISO_UNUSED);
+        } else if angle {
+            moved.extend(ANGLE_ANSI);
+        }
+        if wide {
+            moved.extend(WIDE);
+            unused.extend(WIDE_UNUSED);
+        }
+        if wide && board == Board::Jis {
+            moved.extend(WIDE_JIS);
+        }
+
+        let codes = moved.iter().map(|(code, ..)| *code).chain(unused);
+        for code in codes {
+            self.remove(code.parse().unwrap());
+        }
+        for (code, row, col, finger) in moved {
+            self.insert(code.parse().unwrap(), MappedKey { row, col, finger });
+        }
+
+        self.name = format!("{} {keyboard_mod}", self.name);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(map: &KeyMap, code: &str) -> Option<(isize, isize, Finger)> {
+        map.get(code.parse().unwrap())
+            .map(|key| (key.row, key.col, key.finger))
+    }
+
+    fn preset(name: &str, keyboard_mod: KeyboardMod, board: Board) -> KeyMap {
+        KeyMap::preset(name).unwrap().with_mod(keyboard_mod, board)
+    }
+
+    #[test]
+    fn angle_moves_the_left_bottom_row() {
+        let ansi = preset("ansi", KeyboardMod::Angle, Board::Ansi);
+        assert_eq!(ansi.name, "ansi angle");
+        assert_eq!(key(&ansi, "KeyB"), Some((2, 0, Finger::LI)));
+        assert_eq!(key(&ansi, "KeyZ"), Some((2, 1, Finger::LR)));
+        assert_eq!(key(&ansi, "KeyV"), Some((2, 4, Finger::LI)));
+
+        let iso = preset("iso", KeyboardMod::Angle, Board::Iso);
+        assert_eq!(key(&iso, "KeyB"), None);
+        assert_eq!(key(&iso, "IntlBackslash"), Some((2, 0, Finger::LP)));
+        assert_eq!(key(&iso, "KeyZ"), Some((2, 1, Finger::LR)));
+    }
+
+    #[test]
+    fn wide_moves_the_right_hand() {
+        let ansi = preset("ansi", KeyboardMod::Wide, Board::Ansi);
+        assert_eq!(key(&ansi, "KeyY"), None);
+        assert_eq!(key(&ansi, "KeyU"), Some((0, 5, Finger::RI)));
+        assert_eq!(key(&ansi, "Slash"), Some((2, 8, Finger::RR)));
+        assert_eq!(key(&ansi, "IntlRo"), None);
+
+        let jis = preset("jis", KeyboardMod::Wide, Board::Jis);
+        assert_eq!(key(&jis, "Slash"), Some((2, 8, Finger::RR)));
+        assert_eq!(key(&jis, "IntlRo"), Some((2, 9, Finger::RP)));
+
+        let both = preset("ansi", KeyboardMod::AngleWide, Board::Ansi);
+        assert_eq!(key(&both, "KeyB"), Some((2, 0, Finger::LI)));
+        assert_eq!(key(&both, "KeyN"), None);
+    }
+
+    #[test]
+    fn only_row_staggered_boards_are_modded() {
+        for (name, board) in [("ortho", Board::Ortho), ("colstag", Board::Colstag)] {
+            let map = preset(name, KeyboardMod::AngleWide, board);
+            assert_eq!(map, KeyMap::preset(name).unwrap());
+        }
+        let none = preset("ansi", KeyboardMod::None, Board::Ansi);
+        assert_eq!(none, KeyMap::default());
+    }
+}
@@ -0,0 +1,462 @@
+//! Outlier rejection, applied to a [`MatrixData`] before computing its stats.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+};
+
+use crate::{indexes_are_sfr, sample::Micros, MatrixData};
+
+/// Which trimming scope [`OutlierPolicy::Trim`] computes its cutoffs over.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrimScope {
+    Trigram,
+    Category,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum OutlierPolicy {
+    #[default]
+    None,
+    /// Removes samples above a fixed duration.
+    Ceiling(Micros),
+    /// Removes samples outside `k` interquartile ranges below the first or above the third
+    /// quartile of their trigram. Trigrams with fewer than 4 samples are kept as is.
+    Iqr(f64),
+    /// Removes samples whose modified z-score, based on the median absolute deviation of their
+    /// trigram, exceeds the threshold. Trigrams whose samples are mostly identical are kept as is.
+    Mad(f64),
+    /// Removes the given fraction of samples, rounded down, from both ends of every trigram or
+    /// category. Of samples that tie at a cut, only as many as needed are removed.
+    Trim(f64, TrimScope),
+}
+
+impl FromStr for OutlierPolicy {
+    type Err = String;
+
+    /// Parses `none`, `ceiling:<ms>`, `iqr[:<k>]`, `mad[:<threshold>]`, `trim[:<fraction>]` and
+    /// `trim-category[:<fraction>]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        let number = |default: Option<f64>| match arg {
+            Some(arg) => arg
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite() && *n >= 0.0)
+                .ok_or_else(|| format!("invalid number '{arg}' in outlier policy '{s}'")),
+            None => default.ok_or_else(|| format!("outlier policy '{name}' needs a value")),
+        };
+        let fraction = |n: f64| match n < 0.5 {
+            true => Ok(n),
+            false => Err(format!("can't trim {n} from both ends")),
+        };
+
+        match name {
+            "none" => Ok(Self::None),
+            "ceiling" => Micros::from_ms(number(None)?)
+                .map(Self::Ceiling)
+                .ok_or_else(|| format!("invalid ceiling in '{s}'")),
+            "iqr" => Ok(Self::Iqr(number(Some(1.5))?)),
+            "mad" => Ok(Self::Mad(number(Some(3.5))?)),
+            "trim" => Ok(Self::Trim(
+                fraction(number(Some(0.05))?)?,
+                TrimScope::Trigram,
+            )),
+            "trim-category" => Ok(Self::Trim(
+                fraction(number(Some(0.05))?)?,
+                TrimScope::Category,
+            )),
+            _ => Err(format!("unknown outlier policy '{s}'")),
+        }
+    }
+}
+
+impl std::fmt::Display for OutlierPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Ceiling(max) => write!(f, "ceiling:{max}"),
+            Self::Iqr(k) => write!(f, "iqr:{k}"),
+            Self::Mad(threshold) => write!(f, "mad:{threshold}"),
+            Self::Trim(fraction, TrimScope::Trigram) => write!(f, "trim:{fraction}"),
+            Self::Trim(fraction, TrimScope::Category) => write!(f, "trim-category:{fraction}"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CategoryCount {
+    pub samples: usize,
+    pub removed: usize,
+}
+
+/// How many samples an [`OutlierPolicy`] removed, per category.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OutlierReport {
+    pub policy: OutlierPolicy,
+    pub categories: BTreeMap<String, CategoryCount>,
+}
+
+impl OutlierReport {
+    pub fn total(&self) -> CategoryCount {
+        self.categories
+            .values()
+            .fold(CategoryCount::default(), |acc, c| CategoryCount {
+                samples: acc.samples + c.samples,
+                removed: acc.removed + c.removed,
+            })
+    }
+}
+
+impl std::fmt::Display for OutlierReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line = |f: &mut std::fmt::Formatter<'_>, name: &str, c: &CategoryCount| {
+            let percent = match c.samples {
+                0 => 0.0,
+                n => c.removed as f64 / n as f64 * 100.0,
+            };
+            writeln!(
+                f,
+                "{:<16}{:>6} of {:<6} ({percent:.1}%)",
+                format!("{name}:"),
+                c.removed,
+                c.samples
+            )
+        };
+
+        writeln!(f, "Outliers removed ({}):\n", self.policy)?;
+        line(f, "Overall", &self.total())?;
+        writeln!(f)?;
+        for (name, count) in self.categories.iter() {
+            line(f, name, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Linearly interpolated quantile of sorted values, in µs.
+fn quantile(sorted: &[Micros], q: f64) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        len => {
+            let pos = q * (len - 1) as f64;
+            let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+            let (lo, hi) = (sorted[lo].0 as f64, sorted[hi].0 as f64);
+            lo + (hi - lo) * pos.fract()
+        }
+    }
+}
+
+/// The range of values a policy keeps for a set of samples, in µs. Of the samples equal to `lo` or
+/// `hi`, the first `lo_ties` and `hi_ties` are removed as well, so that trimming removes exactly
+/// its share when values tie at the cut.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Bounds {
+    lo: f64,
+    hi: f64,
+    lo_ties: usize,
+    hi_ties: usize,
+}
+
+impl Bounds {
+    fn new(lo: f64, hi: f64) -> Self {
+        Self {
+            lo,
+            hi,
+            lo_ties: 0,
+            hi_ties: 0,
+        }
+    }
+
+    /// Whether to keep the next sample, which is `v`.
+    fn keep(&mut self, v: Micros) -> bool {
+        let v = v.0 as f64;
+        if v < self.lo || v > self.hi {
+            false
+        } else if v == self.lo && self.lo_ties > 0 {
+            self.lo_ties -= 1;
+            false
+        } else if v == self.hi && self.hi_ties > 0 {
+            self.hi_ties -= 1;
+            false
+        } else {
+            true
+        }
+    }
+}
+
+/// The [`Bounds`] a policy keeps for a set of samples, or `None` to keep all of them.
+fn bounds(policy: &OutlierPolicy, values: &[Micros]) -> Option<Bounds> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+
+    match *policy {
+        OutlierPolicy::None => None,
+        OutlierPolicy::Ceiling(max) => Some(Bounds::new(0.0, max.0 as f64)),
+        OutlierPolicy::Iqr(_) | OutlierPolicy::Mad(_) if sorted.len() < 4 => None,
+        OutlierPolicy::Iqr(k) => {
+            let (q1, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+            Some(Bounds::new(q1 - k * (q3 - q1), q3 + k * (q3 - q1)))
+        }
+        OutlierPolicy::Mad(threshold) => {
+            let median = quantile(&sorted, 0.5);
+            let mut deviations = sorted
+                .iter()
+                .map(|v| Micros((v.0 as f64 - median).abs() as u32))
+                .collect::<Vec<_>>();
+            deviations.sort_unstable();
+
+            // A modified z-score of 0.6745 * (x - median) / mad is comparable to a z-score.
+            match quantile(&deviations, 0.5) {
+                0.0 => None,
+                mad => {
+                    let radius = threshold * mad / 0.6745;
+                    Some(Bounds::new(median - radius, median + radius))
+                }
+            }
+        }
+        OutlierPolicy::Trim(fraction, _) => {
+            let cut = (sorted.len() as f64 * fraction).floor() as usize;
+            if cut == 0 {
+                return None;
+            }
+            let (lo, hi) = (sorted[cut], sorted[sorted.len() - 1 - cut]);
+            Some(Bounds {
+                lo: lo.0 as f64,
+                hi: hi.0 as f64,
+                lo_ties: cut - sorted.partition_point(|v| *v < lo),
+                hi_ties: cut - (sorted.len() - sorted.partition_point(|v| *v <= hi)),
+            })
+        }
+    }
+}
+
+/// Category of trigrams containing a modifier, see [`ModifierPolicy::Separate`].
+///
+/// [`ModifierPolicy::Separate`]: crate::modifiers::ModifierPolicy::Separate
+const CHORDED: &str = "Chorded";
+
+impl MatrixData {
+    /// The category a trigram's samples are reported under: `Sfr` or the name of its pattern.
+    pub fn category(&self, indexes: &[usize; 3]) -> String {
+        match indexes_are_sfr(indexes) {
+            true => "Sfr".to_string(),
+            false => format!("{:?}", self.trigram_pattern(indexes)),
+        }
+    }
+
+    /// Removes outliers according to `policy`. Apart from per-category trimming, every trigram is
+    /// considered on its own.
+    pub fn reject_outliers(&mut self, policy: &OutlierPolicy) -> OutlierReport {
+        let mut report = OutlierReport {
+            policy: *policy,
+            ..Default::default()
+        };
+
+        let categories = self
+            .data
+            .iter()
+            .map(|(indexes, _)| (indexes, self.category(&indexes)))
+            .collect::<HashMap<_, _>>();
+
+        let mut values = BTreeMap::<String, Vec<Micros>>::new();
+        let mut trigram_bounds = HashMap::new();
+        for (indexes, samples) in self.data.iter() {
+            let category = &categories[&indexes];
+            match policy {
+                OutlierPolicy::Trim(_, TrimScope::Category) => values
+                    .entry(category.clone())
+                    .or_default()
+                    .extend(samples.values()),
+                _ => {
+                    trigram_bounds.insert(indexes, bounds(policy, samples.values()));
+                }
+            }
+            let count = report.categories.entry(category.clone()).or_default();
+            count.samples += samples.len();
+        }
+
+        let mut category_bounds = BTreeMap::new();
+        if let OutlierPolicy::Trim(_, TrimScope::Category) = policy {
+            if !self.chorded.is_empty() {
+                let chorded = self.chorded.values().flat_map(|samples| samples.values());
+                values.insert(CHORDED.to_string(), chorded.copied().collect());
+            }
+            for (category, values) in values {
+                category_bounds.insert(category, bounds(policy, &values));
+            }
+        }
+
+        let keep = |bounds: Option<&mut Bounds>, v: Micros| match bounds {
+            Some(bounds) => bounds.keep(v),
+            None => true,
+        };
+
+        self.data.retain(|indexes, _, v| {
+            let category = &categories[indexes];
+            let bounds = match policy {
+                OutlierPolicy::Trim(_, TrimScope::Category) => category_bounds.get_mut(category),
+                _ => trigram_bounds.get_mut(indexes),
+            };
+            let kept = keep(bounds.and_then(Option::as_mut), v);
+            if !kept {
+                report.categories.get_mut(category).unwrap().removed += 1;
+            }
+            kept
+        });
+
+        for samples in self.chorded.values_mut() {
+            let mut own;
+            let mut bounds = match policy {
+                OutlierPolicy::Trim(_, TrimScope::Category) => {
+                    category_bounds.get_mut(CHORDED).and_then(Option::as_mut)
+                }
+                _ => {
+                    own = bounds(policy, samples.values());
+                    own.as_mut()
+                }
+            };
+
+            let count = report.categories.entry(CHORDED.to_string()).or_default();
+            count.samples += samples.len();
+            count.removed += samples.retain(|_, v| keep(bounds.as_deref_mut(), v));
+        }
+
+        self.chorded.retain(|_, samples| !samples.is_empty());
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_trigram, sample::Samples, TrigramData};
+
+    fn matrix(trigrams: &[(&str, &[u32])]) -> MatrixData {
+        let mut data = TrigramData::default();
+        let source = data.add_source(None);
+        for (trigram, ms) in trigrams {
+            let values = ms.iter().map(|ms| Micros(ms * 1000)).collect();
+            data.insert(
+                parse_trigram(trigram).unwrap(),
+                Samples::new(source, values),
+            );
+        }
+        data.matrix_3x10()
+    }
+
+    /// The samples left of a single trigram after applying `policy`, in ms.
+    fn kept(ms: &[u32], policy: &str) -> Vec<u32> {
+        let mut matrix = matrix(&[("KeyA,KeyS,KeyD", ms)]);
+        matrix.reject_outliers(&policy.parse().unwrap());
+        let (_, samples) = matrix.data.iter().next().unwrap();
+        samples.values().iter().map(|v| v.0 / 1000).collect()
+    }
+
+    const SAMPLES: &[u32] = &[10, 20, 30, 40, 50, 60, 70, 80, 90, 1000];
+
+    #[test]
+    fn ceiling_keeps_samples_up_to_the_ceiling() {
+        assert_eq!(kept(SAMPLES, "ceiling:90"), &SAMPLES[..9]);
+        assert_eq!(kept(SAMPLES, "ceiling:89.999"), &SAMPLES[..8]);
+    }
+
+    #[test]
+    fn iqr_fences_are_inclusive() {
+        // Q1 = 32.5 and Q3 = 77.5, so the fences are 32.5 - 0.5 * 45 = 10 and 77.5 + 0.5 * 45 = 100.
+        assert_eq!(kept(SAMPLES, "iqr:0.5"), &SAMPLES[..9]);
+        // 1.5 IQR fences are -35 and 145.
+        assert_eq!(kept(SAMPLES, "iqr"), &SAMPLES[..9]);
+        assert_eq!(kept(&[10, 20, 1000], "iqr:0.5"), [10, 20, 1000]);
+    }
+
+    #[test]
+    fn mad_scores_against_the_median() {
+        // The median is 55 and the MAD 25, so a threshold of 1 keeps 55 ± 25 / 0.6745 = 37.07.
+        assert_eq!(kept(SAMPLES, "mad:1"), &SAMPLES[1..9]);
+        // 3.5 keeps 55 ± 129.7.
+        assert_eq!(kept(SAMPLES, "mad"), &SAMPLES[..9]);
+        assert_eq!(kept(&[50, 50, 50, 50, 1000], "mad"), [50, 50, 50, 50, 1000]);
+    }
+
+    #[test]
+    fn trim_removes_exactly_its_fraction_when_values_tie() {
+        assert_eq!(kept(SAMPLES, "trim:0.1"), &SAMPLES[1..9]);
+        assert_eq!(kept(SAMPLES, "trim:0.09"), SAMPLES);
+
+        let ties = [10, 10, 10, 20, 30, 40, 50, 60, 80, 80];
+        assert_eq!(kept(&ties, "trim:0.2"), [10, 20, 30, 40, 50, 60]);
+        assert_eq!(kept(&[50; 5], "trim:0.2"), [50; 3]);
+    }
+
+    #[test]
+    fn report_counts_removed_samples_per_category() {
+        let trigrams: &[(&str, &[u32])] = &[
+            ("KeyA,KeyS,KeyD", &[10, 20, 30, 40, 50]),
+            ("KeyS,KeyD,KeyF", &[60, 70, 80, 90, 100]),
+            ("KeyA,KeyJ,KeyS", &[10, 20, 30, 40, 1000]),
+        ];
+        let categories = |matrix: &MatrixData| {
+            let category = |trigram: &str| {
+                let pos = parse_trigram(trigram).unwrap();
+                let index = |pos| matrix.projection.index(&matrix.keymap, pos).unwrap();
+                matrix.category(&pos.map(index))
+            };
+            trigrams
+                .iter()
+                .map(|(t, _)| category(t))
+                .collect::<Vec<_>>()
+        };
+
+        let mut by_trigram = matrix(trigrams);
+        let names = categories(&by_trigram);
+        assert_eq!(names[0], names[1]);
+        assert_ne!(names[0], names[2]);
+
+        let report = by_trigram.reject_outliers(&"trim:0.1".parse().unwrap());
+        assert_eq!(
+            report.total(),
+            CategoryCount {
+                samples: 15,
+                removed: 0
+            }
+        );
+
+        let mut by_category = matrix(trigrams);
+        let report = by_category.reject_outliers(&"trim-category:0.1".parse().unwrap());
+        let count = |name: &str| report.categories[name];
+        assert_eq!(
+            count(&names[0]),
+            CategoryCount {
+                samples: 10,
+                removed: 2
+            }
+        );
+        assert_eq!(
+            count(&names[2]),
+            CategoryCount {
+                samples: 5,
+                removed: 0
+            }
+        );
+        assert_eq!(
+            report.total(),
+            CategoryCount {
+                samples: 15,
+                removed: 2
+            }
+        );
+        assert_eq!(by_category.data.sample_count(), 13);
+
+        let mut ceiling = matrix(trigrams);
+        let report = ceiling.reject_outliers(&"ceiling:95".parse().unwrap());
+        assert_eq!(report.categories[&names[0]].removed, 1);
+        assert_eq!(report.categories[&names[2]].removed, 1);
+        assert!(report.to_string().contains("Overall:             2 of 15"));
+    }
+}
@@ -0,0 +1,429 @@
+//! Which keys of a [`KeyMap`] are analysed, and how they're numbered in a [`MatrixData`].
+//!
+//! Key maps place keys on a grid whose rows 0 to 2 and columns 0 to 9 are the letter block. Row -1
+//! is the number row, columns -1 and 10 the outer pinky columns and row 3 the thumb keys. A
+//! projection is a list of cells of that grid, and a key's index in the matrix is the position of
+//! its cell in the list. Besides the [`BUILTIN`] ones, projections can be loaded from JSON files:
+//!
+//! ```json
+//! { "name": "3x10 with numbers 1-5", "cells": [[-1, 0], [-1, 1], [-1, 2], [-1, 3], [-1, 4], [0, 0], ..] }
+//! ```
+//!
+//! [`MatrixData`]: crate::MatrixData
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    error::{LoadError, LoadErrorKind},
+    keymap::{KeyMap, MappedKey},
+    mapping::Pos,
+    modifiers::is_modifier,
+    trigram_patterns::Finger,
+};
+
+/// Names of the built in projections.
+pub const BUILTIN: &[&str] = &["3x10", "3x12", "4x10", "3x10+thumbs"];
+
+/// Cells of a grid of `rows` by `cols`, in reading order.
+fn grid(
+    rows: std::ops::RangeInclusive<isize>,
+    cols: std::ops::RangeInclusive<isize>,
+) -> Vec<(isize, isize)> {
+    rows.flat_map(|row| cols.clone().map(move |col| (row, col)))
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Projection {
+    pub name: String,
+    cells: Vec<(isize, isize)>,
+    indexes: HashMap<(isize, isize), usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectionFile {
+    name: Option<String>,
+    cells: Vec<(isize, isize)>,
+}
+
+fn invalid(message: impl Into<String>) -> LoadError {
+    LoadError::new(None, LoadErrorKind::Projection(message.into()))
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::builtin("3x10").unwrap()
+    }
+}
+
+impl Projection {
+    /// A projection of `cells`, in order. Returns `None` if a cell appears twice.
+    pub fn new(name: impl Into<String>, cells: Vec<(isize, isize)>) -> Option<Self> {
+        let mut indexes = HashMap::new();
+        for (i, cell) in cells.iter().enumerate() {
+            if indexes.insert(*cell, i).is_some() {
+                return None;
+            }
+        }
+
+        Some(Self {
+            name: name.into(),
+            cells,
+            indexes,
+        })
+    }
+
+    /// One of the [`BUILTIN`] projections. 3x10 and 3x10+thumbs number the letter block as
+    /// `row * 10 + col`, with the six middle thumb keys after it. 3x12 adds the outer columns, so
+    /// a key is at `row * 12 + col + 1`, and 4x10 puts the number row first, at
+    /// `(row + 1) * 10 + col`.
+    pub fn builtin(name: &str) -> Option<Self> {
+        let cells = match name {
+            "3x10" => grid(0..=2, 0..=9),
+            "3x12" => grid(0..=2, -1..=10),
+            "4x10" => grid(-1..=2, 0..=9),
+            "3x10+thumbs" => {
+                let mut cells = grid(0..=2, 0..=9);
+                cells.extend(grid(3..=3, 2..=7));
+                cells
+            }
+            _ => return None,
+        };
+
+        Self::new(name, cells)
+    }
+
+    /// Loads the builtin projection called `name_or_path`, or a projection file if there is no
+    /// such projection.
+    pub fn load<P: AsRef<Path>>(name_or_path: P) -> Result<Self, LoadError> {
+        let path = name_or_path.as_ref();
+
+        if let Some(projection) = path.to_str().and_then(Self::builtin) {
+            return Ok(projection);
+        }
+        if !path.exists() && path.extension().is_none() {
+            return Err(invalid(format!(
+                "no projection or file called '{}', builtin projections are {}",
+                path.display(),
+                BUILTIN.join(", ")
+            )));
+        }
+
+        File::open(path)
+            .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))
+            .and_then(|f| Self::read(BufReader::new(f)))
+            .map_err(|mut e| {
+                e.path = Some(path.to_path_buf());
+                e
+            })
+    }
+
+    /// Reads a projection in the format described in the [module docs](self).
+    pub fn read<R: Read>(reader: R) -> Result<Self, LoadError> {
+        let file: ProjectionFile =
+            serde_json::from_reader(reader).map_err(|e| LoadError::from_json(None, e, None))?;
+
+        if let Some((row, col)) = file
+            .cells
+            .iter()
+            .find(|(row, col)| !(-1..=3).contains(row) || !(-1..=10).contains(col))
+        {
+            return Err(invalid(format!("cell [{row}, {col}] is outside the grid")));
+        }
+
+        Self::new(file.name.unwrap_or_default(), file.cells)
+            .ok_or_else(|| invalid("cells must not repeat"))
+    }
+
+    /// Number of keys in the matrix.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// The cell of the key at a matrix index.
+    pub fn cell(&self, index: usize) -> (isize, isize) {
+        self.cells[index]
+    }
+
+    /// Matrix index of a key, or `None` if its cell isn't part of the projection.
+    pub fn key_index(&self, key: MappedKey) -> Option<usize> {
+        self.indexes.get(&key.cell()).copied()
+    }
+
+    /// Matrix index of the key `pos` is mapped to by `keymap`.
+    pub fn index(&self, keymap: &KeyMap, pos: Pos) -> Option<usize> {
+        keymap.get(pos).and_then(|key| self.key_index(key))
+    }
+
+    /// The finger that presses the key at a matrix index according to `keymap`.
+    pub fn finger(&self, keymap: &KeyMap, index: usize) -> Finger {
+        keymap.finger(self.cell(index))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrigramCount {
+    pub trigrams: usize,
+    pub samples: usize,
+}
+
+impl TrigramCount {
+    pub(crate) fn add(&mut self, samples: usize) {
+        self.trigrams += 1;
+        self.samples += samples;
+    }
+}
+
+/// A key that shares its matrix index with other keys, so their trigrams are merged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Remap {
+    pub index: usize,
+    /// The other keys at the same index.
+    pub shared_with: Vec<String>,
+    /// Kept trigrams containing the key.
+    pub count: TrigramCount,
+}
+
+/// What projecting a dataset kept and what it threw away.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProjectionAudit {
+    pub projection: String,
+    /// Name of the key map, which includes the mod it was adjusted for.
+    pub keymap: String,
+    pub kept: TrigramCount,
+    /// Kept trigrams with samples that had a modifier folded into them, see
+    /// [`ModifierPolicy::Fold`](crate::modifiers::ModifierPolicy::Fold). Only the folded samples
+    /// are counted.
+    pub folded: TrigramCount,
+    /// Trigrams containing a modifier that are reported separately.
+    pub chorded: TrigramCount,
+    pub dropped: TrigramCount,
+    /// Dropped trigrams by every key that caused them to be dropped, so a trigram with two keys
+    /// outside of the projection is counted twice.
+    pub dropped_by_key: BTreeMap<String, TrigramCount>,
+    pub remapped: BTreeMap<String, Remap>,
+}
+
+impl ProjectionAudit {
+    pub(crate) fn new(projection: &Projection, keymap: &KeyMap) -> Self {
+        let mut by_index = BTreeMap::<usize, Vec<String>>::new();
+        for (pos, key) in keymap.keys().filter(|(pos, _)| !is_modifier(*pos)) {
+            if let Some(index) = projection.key_index(key) {
+                by_index.entry(index).or_default().push(pos.to_string());
+            }
+        }
+        by_index.values_mut().for_each(|keys| keys.sort());
+
+        let mut remapped = BTreeMap::new();
+        for (index, keys) in by_index.into_iter().filter(|(_, keys)| keys.len() > 1) {
+            for key in keys.iter() {
+                let remap = Remap {
+                    index,
+                    shared_with: keys.iter().filter(|k| *k != key).cloned().collect(),
+                    count: TrigramCount::default(),
+                };
+                remapped.insert(key.clone(), remap);
+            }
+        }
+
+        Self {
+            projection: projection.name.clone(),
+            keymap: keymap.name.clone(),
+            remapped,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn keep(&mut self, trigram: &[Pos; 3], samples: usize) {
+        self.kept.add(samples);
+        for (i, pos) in trigram.iter().enumerate() {
+            if trigram[..i].contains(pos) {
+                continue;
+            }
+            if let Some(remap) = self.remapped.get_mut(&pos.to_string()) {
+                remap.count.add(samples);
+            }
+        }
+    }
+
+    /// Records a dropped trigram, blaming the keys `offending` returns true for.
+    pub(crate) fn drop(
+        &mut self,
+        trigram: &[Pos; 3],
+        samples: usize,
+        offending: impl Fn(Pos) -> bool,
+    ) {
+        self.dropped.add(samples);
+        for (i, pos) in trigram.iter().enumerate() {
+            if offending(*pos) && !trigram[..i].contains(pos) {
+                self.dropped_by_key
+                    .entry(pos.to_string())
+                    .or_default()
+                    .add(samples);
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ProjectionAudit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line = |f: &mut std::fmt::Formatter<'_>, name: &str, c: &TrigramCount| {
+            writeln!(
+                f,
+                "{:<16}{:>6} trigrams {:>8} samples",
+                format!("{name}:"),
+                c.trigrams,
+                c.samples
+            )
+        };
+
+        writeln!(f, "Projection ({}, {}):\n", self.projection, self.keymap)?;
+        line(f, "Kept", &self.kept)?;
+        if self.folded.trigrams > 0 {
+            line(f, "  Folded", &self.folded)?;
+        }
+        if self.chorded.trigrams > 0 {
+            line(f, "Chorded", &self.chorded)?;
+        }
+        line(f, "Dropped", &self.dropped)?;
+
+        if !self.dropped_by_key.is_empty() {
+            writeln!(f, "\nDropped by key:")?;
+            let mut keys = self.dropped_by_key.iter().collect::<Vec<_>>();
+            keys.sort_by_key(|(_, count)| std::cmp::Reverse(count.samples));
+            for (key, count) in keys {
+                line(f, key, count)?;
+            }
+        }
+
+        if !self.remapped.is_empty() {
+            writeln!(f, "\nRemapped:")?;
+            for (key, remap) in self.remapped.iter() {
+                writeln!(
+                    f,
+                    "{:<16}{:>6} trigrams {:>8} samples  index {} with {}",
+                    format!("{key}:"),
+                    remap.count.trigrams,
+                    remap.count.samples,
+                    remap.index,
+                    remap.shared_with.join(", ")
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        modifiers::ModifierPolicy,
+        sample::{Micros, Samples},
+        TrigramData,
+    };
+
+    fn count(trigrams: usize, samples: usize) -> TrigramCount {
+        TrigramCount { trigrams, samples }
+    }
+
+    #[test]
+    fn builtins_number_keys_by_their_cell() {
+        let ansi = KeyMap::default();
+        let index = |name: &str, code: &str| {
+            Projection::builtin(name)
+                .unwrap()
+                .index(&ansi, code.parse().unwrap())
+        };
+
+        assert_eq!(index("3x10", "KeyQ"), Some(0));
+        assert_eq!(index("3x10", "KeyA"), Some(10));
+        assert_eq!(index("3x10", "Slash"), Some(29));
+        assert_eq!(index("3x10", "Tab"), None);
+
+        assert_eq!(index("3x12", "Tab"), Some(0));
+        assert_eq!(index("3x12", "KeyQ"), Some(1));
+        assert_eq!(index("3x12", "Quote"), Some(23));
+        assert_eq!(index("3x12", "Slash"), Some(34));
+
+        assert_eq!(index("4x10", "Digit1"), Some(0));
+        assert_eq!(index("4x10", "KeyQ"), Some(10));
+        assert_eq!(index("4x10", "Slash"), Some(39));
+
+        assert_eq!(index("3x10+thumbs", "Slash"), Some(29));
+        assert_eq!(index("3x10+thumbs", "Space"), Some(33));
+        assert_eq!(Projection::builtin("5x10"), None);
+    }
+
+    #[test]
+    fn audits_count_what_was_kept_dropped_and_remapped() {
+        let mut data = TrigramData::default();
+        let source = data.add_source(None);
+        for (trigram, values) in [
+            ("KeyQ,KeyW,KeyE", &[100, 110][..]),
+            ("Digit1,KeyQ,KeyW", &[90]),
+            ("Tab,Quote,KeyA", &[80, 85, 95]),
+            ("Space,KeyA,KeyS", &[70]),
+            ("KeyA,ShiftLeft,KeyS", &[60]),
+        ] {
+            let values = values.iter().map(|ms| Micros(ms * 1000)).collect();
+            data.insert(
+                crate::parse_trigram(trigram).unwrap(),
+                Samples::new(source, values),
+            );
+        }
+        let audit = |name: &str, keymap: KeyMap| {
+            let projection = Projection::builtin(name).unwrap();
+            let (_, audit) = data
+                .clone()
+                .project(projection, keymap, ModifierPolicy::Drop)
+                .unwrap();
+            audit
+        };
+
+        let narrow = audit("3x10", KeyMap::default());
+        assert_eq!(narrow.kept, count(1, 2));
+        assert_eq!(narrow.dropped, count(4, 6));
+        assert_eq!(
+            narrow.dropped_by_key,
+            BTreeMap::from(
+                [
+                    ("Digit1", count(1, 1)),
+                    ("Quote", count(1, 3)),
+                    ("ShiftLeft", count(1, 1)),
+                    ("Space", count(1, 1)),
+                    ("Tab", count(1, 3)),
+                ]
+                .map(|(key, count)| (key.to_string(), count))
+            )
+        );
+        assert!(narrow.remapped.is_empty());
+
+        for name in ["3x12", "4x10", "3x10+thumbs"] {
+            let wider = audit(name, KeyMap::default());
+            assert_eq!(wider.kept.trigrams, 2, "{name}");
+            assert_eq!(wider.dropped.trigrams, 3, "{name}");
+        }
+
+        let mut keymap = KeyMap::default();
+        keymap.insert("Digit1".parse().unwrap(), MappedKey::new(0, 0));
+        let remapped = audit("3x10", keymap);
+        assert_eq!(remapped.kept, count(2, 3));
+        assert_eq!(remapped.remapped["Digit1"].count, count(1, 1));
+        assert_eq!(remapped.remapped["KeyQ"].count, count(2, 3));
+        assert_eq!(remapped.remapped["KeyQ"].shared_with, ["Digit1"]);
+    }
+}
@@ -0,0 +1,334 @@
+//! Redacting datasets before they're shared. The extension records every keydown on a page, so a
+//! dataset can contain fragments of passwords or private messages typed outside of a typing test.
+
+use std::{collections::HashMap, str::FromStr};
+
+use crate::{
+    events::{EventLog, KeyEvent},
+    keymap::{KeyMap, PRESET_KEYS},
+    mapping::Pos,
+    sample::Source,
+    trigram_patterns::Finger,
+    TrigramData,
+};
+
+/// Coarser classes keys can be re-keyed into, so that a dataset no longer says which key was
+/// pressed. Keys are classified by where the key map puts them and the finger it assigns, and
+/// replaced by the representative key of their class in the preset layout, which keeps the data
+/// loadable as usual. Space stays as is and any other key outside the letter block is dropped.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyClass {
+    /// Every key typed by a finger becomes the home row key of that finger.
+    Finger,
+    /// Every key becomes the key of its finger's home column on the same row, e.g. `KeyT` becomes
+    /// `KeyR`.
+    Position,
+}
+
+impl FromStr for KeyClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "finger" => Ok(Self::Finger),
+            "position" => Ok(Self::Position),
+            _ => Err(format!(
+                "unknown key class '{s}', expected finger or position"
+            )),
+        }
+    }
+}
+
+/// The letter block column a finger rests on.
+fn home_column(finger: Finger) -> Option<isize> {
+    match finger {
+        Finger::LP => Some(0),
+        Finger::LR => Some(1),
+        Finger::LM => Some(2),
+        Finger::LI => Some(3),
+        Finger::RI => Some(6),
+        Finger::RM => Some(7),
+        Finger::RR => Some(8),
+        Finger::RP => Some(9),
+        Finger::LT | Finger::RT => None,
+    }
+}
+
+/// The key the presets put in a cell.
+fn preset_key(row: isize, col: isize) -> Option<Pos> {
+    let (code, ..) = PRESET_KEYS
+        .iter()
+        .find(|(_, r, c)| (*r, *c) == (row, col))?;
+    code.parse().ok()
+}
+
+impl KeyClass {
+    /// The representative key of the class `pos` falls into according to `keymap`, if any.
+    pub fn classify(self, pos: Pos, keymap: &KeyMap) -> Option<Pos> {
+        const SPACE: Pos = Pos { row: 4, col: 3 };
+
+        if pos == SPACE {
+            return Some(SPACE);
+        }
+
+        let key = keymap.get(pos)?;
+        if !(0..3).contains(&key.row) || !(0..10).contains(&key.col) {
+            return None;
+        }
+        let home = home_column(key.finger)?;
+
+        match self {
+            Self::Finger => preset_key(1, home),
+            Self::Position => preset_key(key.row, home),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RedactOptions {
+    /// Trigrams with fewer samples than this are dropped.
+    pub min_samples: usize,
+    /// Drop everything involving keys other than `KeyA` through `KeyZ`.
+    pub letters_only: bool,
+    pub rekey: Option<KeyClass>,
+    /// The key map keys are classified with. Trigram data defaults to the one for its metadata,
+    /// see [`KeyMap::for_metadata`], and event logs to the default map.
+    pub keymap: Option<KeyMap>,
+    /// Keep the `typist` metadata field, which is cleared by default.
+    pub keep_typist: bool,
+}
+
+impl Default for RedactOptions {
+    fn default() -> Self {
+        Self {
+            min_samples: 5,
+            letters_only: false,
+            rekey: None,
+            keymap: None,
+            keep_typist: false,
+        }
+    }
+}
+
+impl RedactOptions {
+    /// What `pos` is replaced with, or `None` if it's removed.
+    fn key(&self, pos: Pos, keymap: &KeyMap) -> Option<Pos> {
+        if self.letters_only && !pos.to_string().starts_with("Key") {
+            return None;
+        }
+
+        match self.rekey {
+            Some(class) => class.classify(pos, keymap),
+            None => Some(pos),
+        }
+    }
+}
+
+/// What redaction removed. For trigram data these are counts of samples, for event logs counts of
+/// events.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RedactReport {
+    /// Removed because of `letters_only` or because the key has no class.
+    pub keys: usize,
+    /// Removed because their trigram had fewer than `min_samples` samples. For event logs this
+    /// includes events that aren't part of any trigram after stripping keys.
+    pub rare: usize,
+    pub rare_trigrams: usize,
+}
+
+impl std::fmt::Display for RedactReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Removed by key:   {}", self.keys)?;
+        writeln!(f, "Removed as rare:  {}", self.rare)?;
+        write!(f, "Rare trigrams:    {}", self.rare_trigrams)
+    }
+}
+
+impl TrigramData {
+    /// Strips and re-keys keys according to `options`, then drops trigrams that are too rare.
+    /// Sources are renamed to `source <n>`, since their names are usually local paths, and the
+    /// typist is removed from the metadata unless `keep_typist` is set.
+    pub fn redact(&self, options: &RedactOptions) -> (Self, RedactReport) {
+        let keymap = match &options.keymap {
+            Some(keymap) => keymap.clone(),
+            None => KeyMap::for_metadata(&self.metadata),
+        };
+        let mut report = RedactReport::default();
+        let mut redacted = Self {
+            metadata: self.metadata.clone(),
+            sources: self
+                .sources
+                .iter()
+                .enumerate()
+                .map(|(i, source)| Source {
+                    name: Some(format!("source {}", i + 1)),
+                    metadata: source.metadata.clone(),
+                })
+                .collect(),
+            trigrams: HashMap::new(),
+            folded: None,
+        };
+        if !options.keep_typist {
+            redacted.metadata.typist = None;
+            for source in redacted.sources.iter_mut() {
+                source.metadata.typist = None;
+            }
+        }
+
+        for (trigram, samples) in self.trigrams.iter() {
+            match trigram.map(|pos| options.key(pos, &keymap)) {
+                [Some(a), Some(b), Some(c)] => redacted.insert([a, b, c], samples.clone()),
+                _ => report.keys += samples.len(),
+            }
+        }
+
+        redacted.trigrams.retain(|_, samples| {
+            let keep = samples.len() >= options.min_samples;
+            if !keep {
+                report.rare += samples.len();
+                report.rare_trigrams += 1;
+            }
+            keep
+        });
+
+        (redacted, report)
+    }
+}
+
+impl EventLog {
+    /// Strips and re-keys events according to `options`, then drops events so that every trigram
+    /// left in the log occurs at least `min_samples` times. Wherever events are dropped the session
+    /// is split into `<session>#<n>`, so no trigrams are formed across the gap. An event between
+    /// two kept trigrams separated by a rare one appears in both parts.
+    pub fn redact(&self, options: &RedactOptions) -> (Self, RedactReport) {
+        let keymap = options.keymap.clone().unwrap_or_default();
+        let mut report = RedactReport::default();
+        let mut segments = Vec::<(&str, Vec<KeyEvent>)>::new();
+
+        for (session, events) in self.sessions() {
+            let mut segment = Vec::new();
+
+            for event in events {
+                match event
+                    .code
+                    .parse()
+                    .ok()
+                    .and_then(|pos| options.key(pos, &keymap))
+                {
+                    Some(pos) => segment.push(KeyEvent {
+                        code: pos.to_string(),
+                        ..event.clone()
+                    }),
+                    None => {
+                        report.keys += 1;
+                        segments.push((session, std::mem::take(&mut segment)));
+                    }
+                }
+            }
+            segments.push((session, segment));
+        }
+
+        let mut counts = HashMap::<[&str; 3], usize>::new();
+        for (_, events) in segments.iter() {
+            for window in events.windows(3) {
+                let trigram = [0, 1, 2].map(|i| window[i].code.as_str());
+                *counts.entry(trigram).or_default() += 1;
+            }
+        }
+        let common = |window: &[KeyEvent]| {
+            let trigram = [0, 1, 2].map(|i| window[i].code.as_str());
+            counts[&trigram] >= options.min_samples
+        };
+
+        report.rare_trigrams = counts
+            .values()
+            .filter(|n| **n < options.min_samples)
+            .count();
+        let mut parts = HashMap::<&str, usize>::new();
+        let mut redacted = EventLog::default();
+
+        for (session, events) in segments.iter() {
+            let mut kept = vec![false; events.len()];
+            let mut start = None;
+
+            for (i, window) in events.windows(3).enumerate() {
+                match (common(window), start) {
+                    (true, None) => start = Some(i),
+                    (false, Some(s)) => {
+                        push_part(&mut redacted, &mut parts, session, &events[s..i + 2]);
+                        kept[s..i + 2].fill(true);
+                        start = None;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(s) = start {
+                push_part(&mut redacted, &mut parts, session, &events[s..]);
+                kept[s..].fill(true);
+            }
+
+            report.rare += kept.iter().filter(|k| !**k).count();
+        }
+
+        (redacted, report)
+    }
+}
+
+/// Appends `events` to `log` as the next part of `session`.
+fn push_part<'a>(
+    log: &mut EventLog,
+    parts: &mut HashMap<&'a str, usize>,
+    session: &'a str,
+    events: &[KeyEvent],
+) {
+    let part = parts.entry(session).or_default();
+    *part += 1;
+
+    log.events.extend(events.iter().map(|event| KeyEvent {
+        session: format!("{session}#{part}"),
+        ..event.clone()
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{geometry::Board, mods::KeyboardMod, testing::fixture};
+
+    fn classify(class: KeyClass, code: &str, keymap: &KeyMap) -> Option<String> {
+        class
+            .classify(code.parse().unwrap(), keymap)
+            .map(|pos| pos.to_string())
+    }
+
+    #[test]
+    fn keys_are_classified_through_the_key_map() {
+        let ansi = KeyMap::default();
+        let position = |code| classify(KeyClass::Position, code, &ansi);
+        assert_eq!(position("KeyT").as_deref(), Some("KeyR"));
+        assert_eq!(position("KeyY").as_deref(), Some("KeyU"));
+        assert_eq!(position("KeyB").as_deref(), Some("KeyV"));
+        assert_eq!(position("Space").as_deref(), Some("Space"));
+        assert_eq!(position("Digit1"), None);
+
+        let angle = KeyMap::default().with_mod(KeyboardMod::Angle, Board::Ansi);
+        let finger = |code| classify(KeyClass::Finger, code, &angle);
+        assert_eq!(finger("KeyZ").as_deref(), Some("KeyS"));
+        assert_eq!(finger("KeyB").as_deref(), Some("KeyF"));
+        assert_eq!(finger("KeyC").as_deref(), Some("KeyF"));
+    }
+
+    #[test]
+    fn sources_and_typist_are_anonymised() {
+        let (redacted, _) = fixture().redact(&RedactOptions::default());
+        assert_eq!(redacted.metadata.typist, None);
+        assert_eq!(redacted.sources[0].name.as_deref(), Some("source 1"));
+
+        let options = RedactOptions {
+            keep_typist: true,
+            ..Default::default()
+        };
+        let (redacted, _) = fixture().redact(&options);
+        assert_eq!(redacted.metadata.typist.as_deref(), Some("maya"));
+    }
+}
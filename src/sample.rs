@@ -0,0 +1,243 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A trigram duration in whole microseconds.
+///
+/// The collector records fractional `performance.now()` differences in milliseconds, so that is
+/// what datasets store; integer millisecond files from older collectors load unchanged.
+#[derive(Copy, Clone, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Micros(pub u32);
+
+impl Micros {
+    /// The longest duration a sample can have, an hour.
+    pub const MAX: Self = Self(3_600_000_000);
+
+    /// Rounds a duration in milliseconds to the nearest microsecond. Returns `None` for negative,
+    /// non-finite or absurdly long (over an hour) durations.
+    pub fn from_ms(ms: f64) -> Option<Self> {
+        let us = (ms * 1000.0).round();
+        (us.is_finite() && (0.0..=Self::MAX.0 as f64).contains(&us)).then_some(Self(us as u32))
+    }
+
+    pub fn as_ms(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+}
+
+impl std::fmt::Display for Micros {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_ms())
+    }
+}
+
+impl<'de> Deserialize<'de> for Micros {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ms = f64::deserialize(deserializer)?;
+        Self::from_ms(ms)
+            .ok_or_else(|| de::Error::custom(format!("invalid trigram duration: {ms} ms")))
+    }
+}
+
+impl Serialize for Micros {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 % 1000 {
+            0 => serializer.serialize_u32(self.0 / 1000),
+            _ => serializer.serialize_f64(self.as_ms()),
+        }
+    }
+}
+
+/// Index into [`TrigramData::sources`](crate::TrigramData::sources).
+pub type SourceId = u32;
+
+/// Where a set of samples came from, usually a single file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Source {
+    pub name: Option<String>,
+    pub metadata: crate::metadata::DatasetMetadata,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "<unnamed>"),
+        }
+    }
+}
+
+/// The samples of a single trigram, together with the source each of them came from. Samples
+/// from the same source are almost always contiguous, so sources are stored as runs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Samples {
+    values: Vec<Micros>,
+    runs: Vec<(SourceId, usize)>,
+}
+
+impl Samples {
+    pub fn new(source: SourceId, values: Vec<Micros>) -> Self {
+        let runs = match values.len() {
+            0 => Vec::new(),
+            len => vec![(source, len)],
+        };
+
+        Self { values, runs }
+    }
+
+    /// Builds samples from values and `(source, len)` runs whose lengths add up to the number of
+    /// values.
+    pub(crate) fn from_runs(values: Vec<Micros>, runs: Vec<(SourceId, usize)>) -> Self {
+        debug_assert_eq!(runs.iter().map(|(_, len)| len).sum::<usize>(), values.len());
+        Self { values, runs }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn values(&self) -> &[Micros] {
+        &self.values
+    }
+
+    /// The source of every sample, as `(source, len)` runs.
+    pub fn runs(&self) -> &[(SourceId, usize)] {
+        &self.runs
+    }
+
+    pub fn append(&mut self, other: &mut Self) {
+        self.values.append(&mut other.values);
+
+        for run in other.runs.drain(..) {
+            match self.runs.last_mut() {
+                Some((source, len)) if *source == run.0 => *len += run.1,
+                _ => self.runs.push(run),
+            }
+        }
+    }
+
+    /// Every sample along with the source it came from.
+    pub fn iter(&self) -> impl Iterator<Item = (SourceId, Micros)> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|&(source, len)| std::iter::repeat_n(source, len))
+            .zip(self.values.iter().copied())
+    }
+
+    /// The samples whose source matches `f`.
+    pub fn filter_source<'a>(
+        &'a self,
+        f: impl Fn(SourceId) -> bool + 'a,
+    ) -> impl Iterator<Item = Micros> + 'a {
+        self.iter()
+            .filter_map(move |(source, value)| f(source).then_some(value))
+    }
+
+    /// Keeps only the samples for which `f` returns true, and returns how many were removed.
+    pub fn retain(&mut self, mut f: impl FnMut(SourceId, Micros) -> bool) -> usize {
+        let before = self.len();
+        let runs = std::mem::take(&mut self.runs);
+        let mut kept = 0;
+        let mut start = 0;
+
+        for (source, len) in runs {
+            for i in start..start + len {
+                let value = self.values[i];
+                if !f(source, value) {
+                    continue;
+                }
+                self.values[kept] = value;
+                kept += 1;
+                match self.runs.last_mut() {
+                    Some((last, len)) if *last == source => *len += 1,
+                    _ => self.runs.push((source, 1)),
+                }
+            }
+            start += len;
+        }
+
+        self.values.truncate(kept);
+        before - kept
+    }
+
+    /// Shifts every source id by `offset`, for when the sources of this dataset are appended to
+    /// those of another.
+    pub(crate) fn offset_sources(&mut self, offset: SourceId) {
+        for (source, _) in self.runs.iter_mut() {
+            *source += offset;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_are_rounded_to_the_microsecond_within_bounds() {
+        assert_eq!(Micros::from_ms(86.25), Some(Micros(86_250)));
+        assert_eq!(Micros::from_ms(0.0004), Some(Micros(0)));
+        assert_eq!(Micros::from_ms(0.0006), Some(Micros(1)));
+        assert_eq!(Micros::from_ms(3_600_000.0), Some(Micros::MAX));
+        assert_eq!(Micros::from_ms(3_600_000.001), None);
+        assert_eq!(Micros::from_ms(-1.0), None);
+        assert_eq!(Micros::from_ms(f64::NAN), None);
+        assert_eq!(Micros::from_ms(f64::INFINITY), None);
+
+        let json = serde_json::to_string(&[Micros(86_000), Micros(86_250)]).unwrap();
+        assert_eq!(json, "[86,86.25]");
+        let parsed: Vec<Micros> = serde_json::from_str("[86, 86.25, 0.001]").unwrap();
+        assert_eq!(parsed, [Micros(86_000), Micros(86_250), Micros(1)]);
+        assert!(serde_json::from_str::<Micros>("-5").is_err());
+    }
+
+    #[test]
+    fn runs_merge_when_sources_continue() {
+        let mut samples = Samples::new(0, vec![Micros(1), Micros(2)]);
+        samples.append(&mut Samples::new(0, vec![Micros(3)]));
+        samples.append(&mut Samples::new(1, vec![Micros(4)]));
+        samples.append(&mut Samples::new(0, vec![]));
+        samples.append(&mut Samples::from_runs(
+            vec![Micros(5), Micros(6)],
+            vec![(1, 1), (0, 1)],
+        ));
+
+        assert_eq!(samples.runs(), [(0, 3), (1, 2), (0, 1)]);
+        assert_eq!(
+            samples.iter().collect::<Vec<_>>(),
+            [
+                (0, Micros(1)),
+                (0, Micros(2)),
+                (0, Micros(3)),
+                (1, Micros(4)),
+                (1, Micros(5)),
+                (0, Micros(6))
+            ]
+        );
+        let from_1 = samples.filter_source(|s| s == 1).collect::<Vec<_>>();
+        assert_eq!(from_1, [Micros(4), Micros(5)]);
+
+        samples.offset_sources(2);
+        assert_eq!(samples.runs(), [(2, 3), (3, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn retain_rebuilds_runs_in_place() {
+        let values = (1..=6).map(Micros).collect();
+        let mut samples = Samples::from_runs(values, vec![(0, 2), (1, 2), (0, 2)]);
+
+        assert_eq!(samples.retain(|_, v| v.0 != 3 && v.0 != 4), 2);
+        assert_eq!(
+            samples.values(),
+            [Micros(1), Micros(2), Micros(5), Micros(6)]
+        );
+        assert_eq!(samples.runs(), [(0, 4)]);
+
+        assert_eq!(samples.retain(|_, v| v.0 % 2 == 0), 2);
+        assert_eq!(samples.runs(), [(0, 2)]);
+        assert_eq!(samples.retain(|_, _| false), 2);
+        assert!(samples.is_empty() && samples.runs().is_empty());
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{metadata::SCHEMA_VERSION, TrigramData};
+
+impl TrigramData {
+    /// Writes the dataset to `path`, see [`write`](Self::write).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+
+    /// Writes the dataset in the same one-line-per-trigram layout the extension's
+    /// `get_trigram_data` produces, with trigrams sorted by position so files diff cleanly.
+    /// Datasets with metadata are wrapped in a versioned envelope. Sources aren't written, so a
+    /// saved dataset loads back as a single source.
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let mut trigrams = self.trigrams.iter().collect::<Vec<_>>();
+        trigrams.sort_unstable_by_key(|(trigram, _)| **trigram);
+
+        let indent = match self.metadata.is_empty() {
+            true => "\t",
+            false => {
+                writeln!(w, "{{")?;
+                writeln!(w, "\t\"version\": {SCHEMA_VERSION},")?;
+                writeln!(
+                    w,
+                    "\t\"metadata\": {},",
+                    serde_json::to_string(&self.metadata)?
+                )?;
+                write!(w, "\t\"trigrams\": ")?;
+                "\t\t"
+            }
+        };
+
+        writeln!(w, "{{")?;
+
+        for (i, ([a, b, c], samples)) in trigrams.iter().enumerate() {
+            write!(w, "{indent}\"{a},{b},{c}\": [")?;
+            for (j, sample) in samples.values().iter().enumerate() {
+                match j {
+                    0 => write!(w, "{sample}")?,
+                    _ => write!(w, ", {sample}")?,
+                }
+            }
+            match i + 1 == trigrams.len() {
+                true => writeln!(w, "]")?,
+                false => writeln!(w, "],")?,
+            }
+        }
+
+        match self.metadata.is_empty() {
+            true => writeln!(w, "}}"),
+            false => writeln!(w, "\t}}\n}}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{testing::*, TrigramData};
+
+    #[test]
+    fn save_round_trips() {
+        let data = fixture();
+        let path = temp_path("save.json");
+        data.save(&path).unwrap();
+        let loaded = TrigramData::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.metadata, loaded.metadata);
+        assert_same_samples(&data, &loaded);
+    }
+}
@@ -0,0 +1,382 @@
+//! CSV and TSV import and export, for analysis in spreadsheets and R.
+//!
+//! The long format has one row per sample:
+//!
+//! ```text
+//! key1,key2,key3,sample,duration,source
+//! KeyK,KeyJ,KeyG,0,86,data/dvorak_1k.json
+//! ```
+//!
+//! `sample` is the index of the sample within its trigram and `duration` is in ms. The summary
+//! format has one row per trigram with `n`, `mean`, `median`, `sd` and the trigram `pattern`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{self, BufRead, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    error::{LoadError, LoadErrorKind},
+    indexes_are_sfr,
+    keymap::KeyMap,
+    load::{FileSummary, LoadMode, LoadReport, RejectedTrigram},
+    mapping::Pos,
+    parse_trigram,
+    projection::Projection,
+    sample::{Micros, Samples, SourceId},
+    trigram_patterns::TrigramPattern,
+    Avg, TrigramData,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TableFormat {
+    Csv,
+    Tsv,
+}
+
+impl TableFormat {
+    /// Picks the format from a `.csv` or `.tsv` extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "csv" => Some(Self::Csv),
+            "tsv" => Some(Self::Tsv),
+            _ => None,
+        }
+    }
+
+    fn separator(self) -> char {
+        match self {
+            Self::Csv => ',',
+            Self::Tsv => '\t',
+        }
+    }
+
+    fn write_row<W: Write>(self, w: &mut W, fields: &[&str]) -> io::Result<()> {
+        let sep = self.separator();
+
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                write!(w, "{sep}")?;
+            }
+            match field.contains([sep, '"', '\n', '\r']) {
+                true => write!(w, "\"{}\"", field.replace('"', "\"\""))?,
+                false => write!(w, "{field}")?,
+            }
+        }
+
+        writeln!(w)
+    }
+
+    /// Splits a row into fields, undoing the quoting done by `write_row`.
+    fn split_row(self, line: &str) -> Result<Vec<String>, String> {
+        let sep = self.separator();
+        let mut fields = vec![String::new()];
+        let mut chars = line.chars().peekable();
+        let mut quoted = false;
+
+        while let Some(c) = chars.next() {
+            let field = fields.last_mut().unwrap();
+            match (c, quoted) {
+                ('"', false) if field.is_empty() => quoted = true,
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                ('"', true) => quoted = false,
+                (c, false) if c == sep => fields.push(String::new()),
+                (c, _) => field.push(c),
+            }
+        }
+
+        match quoted {
+            true => Err("unterminated quote".to_string()),
+            false => Ok(fields),
+        }
+    }
+}
+
+fn median(values: &[Micros]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+
+    match sorted.len() {
+        0 => 0.0,
+        len if len % 2 == 1 => sorted[len / 2].as_ms(),
+        len => (sorted[len / 2 - 1].as_ms() + sorted[len / 2].as_ms()) / 2.0,
+    }
+}
+
+impl TrigramData {
+    fn sorted_trigrams(&self) -> Vec<(&[Pos; 3], &Samples)> {
+        let mut trigrams = self.trigrams.iter().collect::<Vec<_>>();
+        trigrams.sort_unstable_by_key(|(trigram, _)| **trigram);
+        trigrams
+    }
+
+    fn source_name(&self, source: SourceId) -> String {
+        self.sources
+            .get(source as usize)
+            .and_then(|s| s.name.clone())
+            .unwrap_or_default()
+    }
+
+    /// Writes every sample as a row of the long format described in the [module docs](crate::table).
+    pub fn write_table<W: Write>(&self, mut w: W, format: TableFormat) -> io::Result<()> {
+        format.write_row(
+            &mut w,
+            &["key1", "key2", "key3", "sample", "duration", "source"],
+        )?;
+
+        for ([a, b, c], samples) in self.sorted_trigrams() {
+            let keys = [a.to_string(), b.to_string(), c.to_string()];
+
+            for (i, (source, value)) in samples.iter().enumerate() {
+                let row = [
+                    &keys[0],
+                    &keys[1],
+                    &keys[2],
+                    &i.to_string(),
+                    &value.to_string(),
+                    &self.source_name(source),
+                ];
+                format.write_row(&mut w, &row.map(String::as_str))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes one row per trigram with summary statistics. `pattern` is empty for trigrams outside
+    /// the 3x10 matrix, and `Sfr` for trigrams that repeat a key.
+    pub fn write_summary<W: Write>(&self, mut w: W, format: TableFormat) -> io::Result<()> {
+        format.write_row(
+            &mut w,
+            &[
+                "key1", "key2", "key3", "n", "mean", "median", "sd", "pattern",
+            ],
+        )?;
+
+        let keymap = KeyMap::for_metadata(&self.metadata);
+        let projection = Projection::default();
+
+        for (trigram, samples) in self.sorted_trigrams() {
+            let [a, b, c] = trigram.map(|pos| projection.index(&keymap, pos));
+            let pattern = match [a, b, c] {
+                [Some(a), Some(b), Some(c)] if indexes_are_sfr(&[a, b, c]) => "Sfr".to_string(),
+                [Some(a), Some(b), Some(c)] => {
+                    let fingers = [a, b, c].map(|index| projection.finger(&keymap, index));
+                    format!("{:?}", TrigramPattern::from_fingers(fingers))
+                }
+                _ => String::new(),
+            };
+
+            let avg = Avg::new(samples.values().to_vec());
+            let row = [
+                trigram[0].to_string(),
+                trigram[1].to_string(),
+                trigram[2].to_string(),
+                avg.pop().to_string(),
+                format!("{:.3}", avg.mean()),
+                format!("{:.3}", median(samples.values())),
+                format!("{:.3}", avg.sd()),
+                pattern,
+            ];
+            format.write_row(&mut w, &row.each_ref().map(String::as_str))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save_table<P: AsRef<Path>>(&self, path: P, format: TableFormat) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_table(&mut w, format)?;
+        w.flush()
+    }
+
+    pub fn save_summary<P: AsRef<Path>>(&self, path: P, format: TableFormat) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_summary(&mut w, format)?;
+        w.flush()
+    }
+
+    /// Reads the long format into `self`. Columns are found by name in the header, and `sample`
+    /// and `source` are optional. Rows without a source are attributed to `name`.
+    pub(crate) fn read_table_into<R: BufRead>(
+        &mut self,
+        reader: R,
+        format: TableFormat,
+        mode: LoadMode,
+        name: Option<String>,
+    ) -> Result<LoadReport, LoadError> {
+        let table_err = |line: usize, message: String| {
+            LoadError::new(None, LoadErrorKind::Table { line, message })
+        };
+
+        let mut lines = reader.lines().enumerate().map(|(i, line)| {
+            line.map(|line| (i + 1, line))
+                .map_err(|e| LoadError::new(None, LoadErrorKind::Io(e)))
+        });
+
+        let Some((_, header)) = lines.next().transpose()? else {
+            return Ok(LoadReport {
+                files: vec![FileSummary::default()],
+                ..Default::default()
+            });
+        };
+        let header = format.split_row(&header).map_err(|e| table_err(1, e))?;
+        let column = |name: &str| header.iter().position(|h| h.trim() == name);
+
+        let (Some(k1), Some(k2), Some(k3), Some(duration)) = (
+            column("key1"),
+            column("key2"),
+            column("key3"),
+            column("duration"),
+        ) else {
+            return Err(table_err(
+                1,
+                "header needs key1, key2, key3 and duration columns".to_string(),
+            ));
+        };
+        let source_column = column("source");
+
+        let mut sources = HashMap::<String, SourceId>::new();
+        let mut report = LoadReport::default();
+        let mut entries = HashSet::new();
+        let mut samples = 0;
+
+        for line in lines {
+            let (line, row) = line?;
+            if row.trim().is_empty() {
+                continue;
+            }
+
+            let fields = format.split_row(&row).map_err(|e| table_err(line, e))?;
+            let field = |i: usize| {
+                fields
+                    .get(i)
+                    .map(|f| f.trim())
+                    .ok_or_else(|| table_err(line, format!("row has only {} fields", fields.len())))
+            };
+
+            let trigram = format!("{},{},{}", field(k1)?, field(k2)?, field(k3)?);
+            let value = field(duration)?;
+            let value = value
+                .parse::<f64>()
+                .ok()
+                .and_then(Micros::from_ms)
+                .ok_or_else(|| table_err(line, format!("invalid duration '{value}'")))?;
+
+            let source_name = match source_column {
+                Some(i) => Some(field(i)?.to_string()).filter(|s| !s.is_empty()),
+                None => None,
+            };
+            let source = match sources.get(&source_name.clone().unwrap_or_default()) {
+                Some(source) => *source,
+                None => {
+                    let source = self.add_source(source_name.clone().or(name.clone()));
+                    sources.insert(source_name.unwrap_or_default(), source);
+                    source
+                }
+            };
+
+            match parse_trigram(&trigram) {
+                Ok(trigram) => {
+                    entries.insert(trigram);
+                    samples += 1;
+                    self.insert(trigram, Samples::new(source, vec![value]));
+                }
+                Err(reason) if mode == LoadMode::Lenient => {
+                    let kind = std::mem::discriminant(&reason);
+                    let group = report
+                        .rejected
+                        .iter_mut()
+                        .find(|r| std::mem::discriminant(&r.reason) == kind);
+                    match group {
+                        Some(group) => group.add_row(line),
+                        None => report.rejected.push(RejectedTrigram {
+                            path: None,
+                            trigram,
+                            samples: 1,
+                            reason,
+                            entries: 1,
+                            lines: vec![line],
+                        }),
+                    }
+                }
+                Err(reason) => return Err(LoadError::new(None, reason)),
+            }
+        }
+
+        report.files.push(FileSummary {
+            path: None,
+            entries: entries.len(),
+            samples,
+        });
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TableFormat;
+    use crate::{error::LoadErrorKind, load::LoadMode, testing::*, TrigramData};
+
+    /// Tables have no room for metadata, only samples and their sources round trip.
+    #[test]
+    fn save_table_round_trips() {
+        let data = fixture();
+        for (format, name) in [
+            (TableFormat::Csv, "save.csv"),
+            (TableFormat::Tsv, "save.tsv"),
+        ] {
+            let path = temp_path(name);
+            data.save_table(&path, format).unwrap();
+            let loaded = TrigramData::load(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_same_samples(&data, &loaded);
+            assert_same_sources(&data, &loaded);
+        }
+    }
+
+    #[test]
+    fn lenient_rejections_are_grouped_by_kind() {
+        let mut csv = "key1,key2,key3,duration\n".to_string();
+        for i in 0..100 {
+            match i % 10 {
+                0 => csv.push_str("KeyA,,KeyC,90\n"),
+                1 | 2 => csv.push_str("KeyA,KeyB,Nope,90\n"),
+                _ => csv.push_str("KeyA,KeyB,KeyC,90\n"),
+            }
+        }
+        let path = temp_path("lenient.csv");
+        std::fs::write(&path, csv).unwrap();
+        let (data, report) = TrigramData::load_with_mode(&path, LoadMode::Lenient).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(data.sample_count(), 70);
+        assert_eq!(report.rejected.len(), 2);
+        assert_eq!(report.rejected_entries(), 30);
+        assert_eq!(report.rejected_samples(), 30);
+
+        let malformed = &report.rejected[0];
+        assert!(matches!(
+            malformed.reason,
+            LoadErrorKind::MalformedTrigram { .. }
+        ));
+        assert_eq!(malformed.entries, 10);
+        assert_eq!(malformed.lines, [2, 12, 22, 32, 42]);
+
+        let unknown = &report.rejected[1];
+        assert!(matches!(unknown.reason, LoadErrorKind::UnknownKey { .. }));
+        assert_eq!(unknown.entries, 20);
+        assert_eq!(unknown.lines, [3, 4, 13, 14, 23]);
+
+        let shown = report.to_string();
+        assert_eq!(shown.lines().count(), 4);
+        assert!(shown.contains("skipped 20 rows on lines 3, 4, 13, 14, 23 and more, like "));
+    }
+}
@@ -0,0 +1,85 @@
+//! Fixtures shared by the tests of several modules.
+
+use std::path::PathBuf;
+
+use crate::{
+    events::{EventLog, Reconstructor},
+    metadata::DatasetMetadata,
+    modifiers::ModifierPolicy,
+    mods::KeyboardMod,
+    sample::{Micros, Samples},
+    TrigramData,
+};
+
+/// `data/dvorak_1k.json` with every metadata field set and a trigram of fractional samples, so
+/// round trips cover more than whole milliseconds.
+pub(crate) fn fixture() -> TrigramData {
+    let mut data = TrigramData::load("data/dvorak_1k.json").unwrap();
+    data.metadata = DatasetMetadata {
+        typist: Some("maya".to_string()),
+        layout: Some("dvorak".to_string()),
+        keyboard: Some("ansi".to_string()),
+        keyboard_mod: Some(KeyboardMod::Angle),
+        source: Some("extension".to_string()),
+        date_range: Some(["2023-04-01".to_string(), "2023-04-12".to_string()]),
+        max_delay: Some(750),
+    };
+
+    let trigram = crate::parse_trigram("KeyQ,KeyQ,KeyQ").unwrap();
+    let values = vec![Micros(86_250), Micros(1), Micros(120_999)];
+    data.insert(trigram, Samples::new(0, values));
+    data
+}
+
+/// A session with `ShiftLeft` held from 100 to 180 ms over `KeyA` and a `ShiftRight` without a
+/// keyup before `KeyE`, reconstructed with `modifiers`.
+pub(crate) fn shifted(modifiers: ModifierPolicy) -> TrigramData {
+    let log = r#"{"code": "ShiftLeft", "down": 100, "up": 180, "session": "a"}
+{"code": "KeyA", "down": 150, "session": "a"}
+{"code": "KeyB", "down": 200, "session": "a"}
+{"code": "KeyC", "down": 260, "session": "a"}
+{"code": "KeyD", "down": 330, "session": "a"}
+{"code": "ShiftRight", "down": 340, "session": "a"}
+{"code": "KeyE", "down": 380, "session": "a"}"#;
+
+    Reconstructor {
+        modifiers,
+        ..Default::default()
+    }
+    .trigrams(&EventLog::read(log.as_bytes()).unwrap())
+}
+
+/// A path in the temporary directory that no other test uses.
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("trigram-timing-data-{}-{name}", std::process::id()))
+}
+
+/// Checks that two datasets have the same samples in the same order.
+pub(crate) fn assert_same_samples(a: &TrigramData, b: &TrigramData) {
+    assert_eq!(a.trigrams.len(), b.trigrams.len());
+    for (trigram, samples) in a.trigrams() {
+        assert_eq!(
+            samples.values(),
+            b.trigrams[trigram].values(),
+            "{trigram:?}"
+        );
+    }
+}
+
+/// Checks that the samples of two datasets with the same samples come from sources of the same
+/// names.
+pub(crate) fn assert_same_sources(a: &TrigramData, b: &TrigramData) {
+    let names = |data: &TrigramData, samples: &Samples| {
+        samples
+            .iter()
+            .map(|(source, _)| data.sources[source as usize].name.clone())
+            .collect::<Vec<_>>()
+    };
+    for (trigram, samples) in a.trigrams() {
+        assert_eq!(
+            names(a, samples),
+            names(b, &b.trigrams[trigram]),
+            "{trigram:?}"
+        );
+    }
+}
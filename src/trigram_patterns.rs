@@ -1,3 +1,5 @@
+#![allow(clippy::match_like_matches_macro)]
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum TrigramPattern {
     Alternate,
@@ -42,7 +44,7 @@ impl From<Finger> for Hand {
 }
 
 #[repr(u8)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub enum Finger {
     LP,
     LR,
@@ -89,6 +91,29 @@ impl Finger {
         (self as u8) < (other as u8)
     }
 
+    /// Order of the fingers from left to right, with the thumbs between the index fingers.
+    const fn rank(self) -> u8 {
+        match self {
+            LP => 0,
+            LR => 1,
+            LM => 2,
+            LI => 3,
+            LT => 4,
+            RT => 5,
+            RI => 6,
+            RM => 7,
+            RR => 8,
+            RP => 9,
+        }
+    }
+
+    const fn is_thumb(self) -> bool {
+        match self {
+            LT | RT => true,
+            _ => false,
+        }
+    }
+
     const fn hand(&self) -> Hand {
         match self {
             LP | LR | LM | LI | LT => Left,
@@ -181,10 +206,10 @@ impl Trigram {
 
     const fn is_inroll(&self) -> bool {
         match (self.h1, self.h2, self.h3) {
-            (Left, Left, Right) => self.f1.lt(self.f2),
-            (Right, Left, Left) => self.f2.lt(self.f3),
-            (Right, Right, Left) => self.f1.gt(self.f2),
-            (Left, Right, Right) => self.f2.gt(self.f3),
+            (Left, Left, Right) => self.f1.rank() < self.f2.rank(),
+            (Right, Left, Left) => self.f2.rank() < self.f3.rank(),
+            (Right, Right, Left) => self.f1.rank() > self.f2.rank(),
+            (Left, Right, Right) => self.f2.rank() > self.f3.rank(),
             _ => unreachable!(),
         }
     }
@@ -207,7 +232,8 @@ impl Trigram {
     }
 
     const fn is_redir(&self) -> bool {
-        (self.f1.lt(self.f2) == self.f2.gt(self.f3)) && self.on_one_hand()
+        ((self.f1.rank() < self.f2.rank()) == (self.f2.rank() > self.f3.rank()))
+            && self.on_one_hand()
     }
 
     const fn is_bad_redir(&self) -> bool {
@@ -282,3 +308,64 @@ const fn get_trigram_combinations() -> [TrigramPattern; 512] {
 }
 
 pub static TRIGRAM_COMBINATIONS: [TrigramPattern; 512] = get_trigram_combinations();
+
+impl TrigramPattern {
+    /// Pattern of a trigram typed with `fingers`. Unlike [`TRIGRAM_COMBINATIONS`] this includes
+    /// the thumbs.
+    pub fn from_fingers([a, b, c]: [Finger; 3]) -> Self {
+        match a.is_thumb() || b.is_thumb() || c.is_thumb() {
+            true => Trigram::new(a, b, c).get_trigram_pattern(),
+            false => TRIGRAM_COMBINATIONS[((a as usize) << 6) | ((b as usize) << 3) | c as usize],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrigramPattern::*, *};
+
+    #[test]
+    fn fingers_are_classified() {
+        let cases = [
+            ([LI, RI, LM], Alternate),
+            ([LI, RI, LI], AlternateSfs),
+            ([LP, LR, RI], Inroll),
+            ([LR, LP, RI], Outroll),
+            ([LP, LR, LM], Onehand),
+            ([LM, LI, LR], Redirect),
+            ([LM, LI, LM], RedirectSfs),
+            ([LR, LM, LP], BadRedirect),
+            ([LR, LM, LR], BadRedirectSfs),
+            ([LI, LI, RI], Sfb),
+            ([LI, LI, LM], BadSfb),
+            ([LI, LI, LI], Sft),
+            ([LT, RI, LI], Alternate),
+            ([LI, LT, RI], Inroll),
+            ([RT, RT, RI], BadSfb),
+        ];
+        for (fingers, pattern) in cases {
+            assert_eq!(
+                TrigramPattern::from_fingers(fingers),
+                pattern,
+                "{fingers:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn the_table_matches_classifying_directly() {
+        for a in 0..8 {
+            for b in 0..8 {
+                for c in 0..8 {
+                    let fingers = [a, b, c].map(Finger::from_usize);
+                    let [f1, f2, f3] = fingers;
+                    assert_eq!(
+                        TrigramPattern::from_fingers(fingers),
+                        Trigram::new(f1, f2, f3).get_trigram_pattern(),
+                        "{fingers:?}"
+                    );
+                }
+            }
+        }
+    }
+}
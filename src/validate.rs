@@ -0,0 +1,377 @@
+//! Checks datasets for entries that are probably junk rather than real typing.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use serde::Serialize;
+
+use crate::{
+    keymap::KeyMap,
+    mapping::{trigram_str, Pos},
+    projection::Projection,
+    sample::{Micros, SourceId},
+    TrigramData,
+};
+
+/// Keys that end up in trigrams while not typing text.
+pub const CONTROL_KEYS: &[&str] = &[
+    "Escape",
+    "Tab",
+    "CapsLock",
+    "Backspace",
+    "Enter",
+    "ControlLeft",
+    "ControlRight",
+    "AltLeft",
+    "AltRight",
+    "MetaLeft",
+    "MetaRight",
+    "ContextMenu",
+];
+
+#[derive(Clone, Debug)]
+pub struct ValidateOptions {
+    /// Durations below this are implausibly fast for three keypresses.
+    pub min_duration: Micros,
+    /// Durations above this are most likely hesitations rather than typing.
+    pub max_duration: Micros,
+    /// More samples than this for a single trigram usually means a key was held down.
+    pub max_samples: usize,
+    /// The key map that decides which keys are outside the 3x10 matrix. Defaults to the one for the
+    /// dataset's metadata, see [`KeyMap::for_metadata`].
+    pub keymap: Option<KeyMap>,
+}
+
+impl Default for ValidateOptions {
+    fn default() -> Self {
+        Self {
+            min_duration: Micros(20_000),
+            max_duration: Micros(500_000),
+            max_samples: 1000,
+            keymap: None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IssueKind {
+    ZeroDuration {
+        trigram: String,
+        samples: usize,
+    },
+    DurationOutOfRange {
+        trigram: String,
+        samples: usize,
+        min_ms: f64,
+        max_ms: f64,
+    },
+    ImplausibleSampleCount {
+        trigram: String,
+        samples: usize,
+    },
+    DuplicateSource {
+        duplicate_of: String,
+    },
+    ControlKey {
+        key: String,
+        trigrams: usize,
+        samples: usize,
+    },
+    OutsideMatrix {
+        key: String,
+        trigrams: usize,
+        samples: usize,
+    },
+}
+
+impl IssueKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::ControlKey { .. } | Self::OutsideMatrix { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Issue {
+    pub severity: Severity,
+    /// The source the issue was found in, `None` if it concerns the dataset as a whole.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(flatten)]
+    pub kind: IssueKind,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ValidationReport {
+    pub errors: usize,
+    pub warnings: usize,
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, source: Option<String>, kind: IssueKind) {
+        match kind.severity() {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+        self.issues.push(Issue {
+            severity: kind.severity(),
+            source,
+            kind,
+        });
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors > 0
+    }
+}
+
+#[derive(Default)]
+struct KeyCount {
+    trigrams: usize,
+    samples: usize,
+}
+
+impl TrigramData {
+    /// Flags suspicious entries. Durations, control keys and keys outside the 3x10 matrix of the
+    /// key map are reported per source; duplicate sources are sources with exactly the same
+    /// samples.
+    pub fn validate(&self, options: &ValidateOptions) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let keymap = match &options.keymap {
+            Some(keymap) => keymap.clone(),
+            None => KeyMap::for_metadata(&self.metadata),
+        };
+        let projection = Projection::default();
+        let source_name =
+            |source: SourceId| self.sources.get(source as usize).map(|s| s.to_string());
+
+        let mut trigrams = self.trigrams().collect::<Vec<_>>();
+        trigrams.sort_unstable_by_key(|(trigram, _)| **trigram);
+
+        let mut fingerprints = HashMap::<SourceId, Vec<([Pos; 3], Micros)>>::new();
+        let mut keys = BTreeMap::<(SourceId, bool, String), KeyCount>::new();
+
+        for (trigram, samples) in trigrams.iter() {
+            let mut per_source = BTreeMap::<SourceId, (usize, usize, usize)>::new();
+
+            for (source, value) in samples.iter() {
+                let (total, zero, out_of_range) = per_source.entry(source).or_default();
+                *total += 1;
+                if value.0 == 0 {
+                    *zero += 1;
+                } else if value < options.min_duration || value > options.max_duration {
+                    *out_of_range += 1;
+                }
+                fingerprints
+                    .entry(source)
+                    .or_default()
+                    .push((**trigram, value));
+            }
+
+            for (&source, &(total, zero, out_of_range)) in per_source.iter() {
+                if zero > 0 {
+                    let kind = IssueKind::ZeroDuration {
+                        trigram: trigram_str(trigram),
+                        samples: zero,
+                    };
+                    report.push(source_name(source), kind);
+                }
+                if out_of_range > 0 {
+                    let kind = IssueKind::DurationOutOfRange {
+                        trigram: trigram_str(trigram),
+                        samples: out_of_range,
+                        min_ms: options.min_duration.as_ms(),
+                        max_ms: options.max_duration.as_ms(),
+                    };
+                    report.push(source_name(source), kind);
+                }
+
+                for (i, pos) in trigram.iter().enumerate() {
+                    if trigram[..i].contains(pos) {
+                        continue;
+                    }
+                    let key = pos.to_string();
+                    let control = CONTROL_KEYS.contains(&key.as_str());
+                    if control || projection.index(&keymap, *pos).is_none() {
+                        let count = keys.entry((source, control, key)).or_default();
+                        count.trigrams += 1;
+                        count.samples += total;
+                    }
+                }
+            }
+
+            if samples.len() > options.max_samples {
+                let kind = IssueKind::ImplausibleSampleCount {
+                    trigram: trigram_str(trigram),
+                    samples: samples.len(),
+                };
+                report.push(None, kind);
+            }
+        }
+
+        for ((source, control, key), count) in keys {
+            let kind = match control {
+                true => IssueKind::ControlKey {
+                    key,
+                    trigrams: count.trigrams,
+                    samples: count.samples,
+                },
+                false => IssueKind::OutsideMatrix {
+                    key,
+                    trigrams: count.trigrams,
+                    samples: count.samples,
+                },
+            };
+            report.push(source_name(source), kind);
+        }
+
+        // Hashes only pick candidates, sources are duplicates if their samples are equal.
+        let mut sources = fingerprints.into_iter().collect::<Vec<_>>();
+        sources.sort_unstable_by_key(|(source, _)| *source);
+        sources
+            .iter_mut()
+            .for_each(|(_, entries)| entries.sort_unstable());
+        let mut buckets = HashMap::<u64, Vec<usize>>::new();
+
+        for (i, (source, entries)) in sources.iter().enumerate() {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            entries.hash(&mut hasher);
+            let bucket = buckets.entry(hasher.finish()).or_default();
+
+            match bucket.iter().find(|j| sources[**j].1 == *entries) {
+                Some(&original) => {
+                    let kind = IssueKind::DuplicateSource {
+                        duplicate_of: source_name(sources[original].0).unwrap_or_default(),
+                    };
+                    report.push(source_name(*source), kind);
+                }
+                None => bucket.push(i),
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{keymap::MappedKey, parse_trigram, sample::Samples, testing::fixture};
+
+    fn duplicates(data: &TrigramData) -> usize {
+        let report = data.validate(&ValidateOptions::default());
+        let is_duplicate = |issue: &&Issue| matches!(issue.kind, IssueKind::DuplicateSource { .. });
+        report.issues.iter().filter(is_duplicate).count()
+    }
+
+    #[test]
+    fn duplicates_need_equal_samples() {
+        let (data, _) = fixture().combine(fixture()).unwrap();
+        assert_eq!(duplicates(&data), 1);
+
+        let mut other = fixture();
+        let trigram = crate::parse_trigram("KeyQ,KeyQ,KeyQ").unwrap();
+        other.insert(trigram, Samples::new(0, vec![Micros(90_000)]));
+        let (data, _) = fixture().combine(other).unwrap();
+        assert_eq!(duplicates(&data), 0);
+    }
+
+    fn ms(ms: &[u32]) -> Vec<Micros> {
+        ms.iter().map(|ms| Micros(ms * 1000)).collect()
+    }
+
+    #[test]
+    fn durations_and_sample_counts_are_checked() {
+        let mut data = TrigramData::default();
+        let source = data.add_source(Some("a".to_string()));
+        let trigram = parse_trigram("KeyA,KeyS,KeyD").unwrap();
+        data.insert(
+            trigram,
+            Samples::new(source, ms(&[0, 10, 20, 100, 500, 501, 0])),
+        );
+        data.insert(
+            parse_trigram("KeyJ,KeyK,KeyL").unwrap(),
+            Samples::new(source, ms(&[100; 4])),
+        );
+
+        let options = ValidateOptions {
+            max_samples: 3,
+            ..Default::default()
+        };
+        let report = data.validate(&options);
+        let kinds = report.issues.iter().map(|i| &i.kind).collect::<Vec<_>>();
+
+        assert_eq!(
+            kinds,
+            [
+                &IssueKind::ZeroDuration {
+                    trigram: "KeyA,KeyS,KeyD".to_string(),
+                    samples: 2,
+                },
+                &IssueKind::DurationOutOfRange {
+                    trigram: "KeyA,KeyS,KeyD".to_string(),
+                    samples: 2,
+                    min_ms: 20.0,
+                    max_ms: 500.0,
+                },
+                &IssueKind::ImplausibleSampleCount {
+                    trigram: "KeyA,KeyS,KeyD".to_string(),
+                    samples: 7,
+                },
+                &IssueKind::ImplausibleSampleCount {
+                    trigram: "KeyJ,KeyK,KeyL".to_string(),
+                    samples: 4,
+                },
+            ]
+        );
+        assert_eq!((report.errors, report.warnings), (4, 0));
+        assert_eq!(report.issues[0].source.as_deref(), Some("a"));
+        assert_eq!(report.issues[2].source, None);
+    }
+
+    #[test]
+    fn keys_outside_the_matrix_follow_the_key_map() {
+        let mut data = TrigramData::default();
+        let source = data.add_source(None);
+        data.insert(
+            parse_trigram("KeyA,Tab,BracketLeft").unwrap(),
+            Samples::new(source, ms(&[100])),
+        );
+
+        let keys = |options: &ValidateOptions| {
+            let report = data.validate(options);
+            let key = |issue: &Issue| match &issue.kind {
+                IssueKind::ControlKey { key, .. } => format!("control {key}"),
+                IssueKind::OutsideMatrix { key, .. } => format!("outside {key}"),
+                kind => panic!("unexpected {kind:?}"),
+            };
+            report.issues.iter().map(key).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            keys(&ValidateOptions::default()),
+            ["outside BracketLeft", "control Tab"]
+        );
+
+        let mut keymap = KeyMap::preset("ansi").unwrap();
+        keymap.remove("KeyA".parse().unwrap());
+        keymap.insert("BracketLeft".parse().unwrap(), MappedKey::new(1, 0));
+        let options = ValidateOptions {
+            keymap: Some(keymap),
+            ..Default::default()
+        };
+        assert_eq!(keys(&options), ["outside KeyA", "control Tab"]);
+    }
+}